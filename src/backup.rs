@@ -0,0 +1,301 @@
+//! Timestamped backups of maps, kept next to them as
+//! `map.wbox.bak.2026-10-17T12-00-00`.
+//!
+//! A backup is made before a map is replaced, and only the newest ones are
+//! kept. Timestamps are in UTC; a second backup within the same second gets
+//! a `-2`, `-3`, ... suffix.
+
+use crate::{
+    atomic::{self, AtomicFile},
+    verify, Map, PressorError, Result,
+};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of backups kept per map unless configured otherwise.
+pub const DEFAULT_KEEP: usize = 5;
+
+const INFIX: &str = ".bak.";
+const TIMESTAMP_LEN: usize = "2026-10-17T12-00-00".len();
+
+/// A backup file of a map.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// When it was made, such as `2026-10-17T12-00-00`, plus the suffix of
+    /// a repeated backup within the same second.
+    pub timestamp: String,
+    pub size: u64,
+}
+
+impl Backup {
+    /// Time and repeat within that second, for sorting.
+    fn order(&self) -> (&str, u32) {
+        let (time, repeat) = self.timestamp.split_at(TIMESTAMP_LEN);
+        match repeat.strip_prefix('-') {
+            Some(repeat) => (time, repeat.parse().unwrap_or(u32::MAX)),
+            None => (time, 1),
+        }
+    }
+}
+
+/// Copies `path` to a new backup and deletes the oldest backups beyond
+/// `keep`. Does nothing if `path` does not exist or `keep` is 0.
+pub fn create(path: &Path, keep: usize) -> Result<Option<PathBuf>> {
+    if keep == 0 || !path.is_file() {
+        return Ok(None);
+    }
+    let error = |e| PressorError::io(format!("Backup of {} failed", path.display()), e);
+    let mut timestamp = timestamp(SystemTime::now());
+    let last_repeat = list(path)?
+        .iter()
+        .map(Backup::order)
+        .filter(|(time, _)| *time == timestamp)
+        .map(|(_, repeat)| repeat)
+        .max();
+    if let Some(repeat) = last_repeat {
+        timestamp = format!("{}-{}", timestamp, repeat + 1);
+    }
+    let backup = backup_path(path, &timestamp);
+    let data = fs::read(path).map_err(error)?;
+    atomic::write(&backup, &data).map_err(error)?;
+    prune(path, keep)?;
+    Ok(Some(backup))
+}
+
+/// Backups of `path`, oldest first.
+pub fn list(path: &Path) -> Result<Vec<Backup>> {
+    let error = |e| PressorError::io(format!("Listing backups of {} failed", path.display()), e);
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+        return Ok(Vec::new());
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}{}", name, INFIX);
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(timestamp) = file_name.strip_prefix(&prefix) else {
+            continue;
+        };
+        if !is_timestamp(timestamp) {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(error)?;
+        if metadata.is_file() {
+            backups.push(Backup {
+                path: path.with_file_name(&file_name),
+                timestamp: timestamp.to_string(),
+                size: metadata.len(),
+            });
+        }
+    }
+    backups.sort_by(|a, b| a.order().cmp(&b.order()));
+    Ok(backups)
+}
+
+/// The backup of `path` that `name` stands for: its number, counting from 1
+/// for the newest, its timestamp or its path. `None` picks the newest.
+pub fn find(path: &Path, name: Option<&str>) -> Result<Option<Backup>> {
+    let mut newest_first = list(path)?.into_iter().rev();
+    let Some(name) = name else {
+        return Ok(newest_first.next());
+    };
+    Ok(newest_first.enumerate().find_map(|(i, backup)| {
+        let matches = name.parse::<usize>() == Ok(i + 1)
+            || name == backup.timestamp
+            || crate::same_file(Path::new(name), &backup.path);
+        matches.then_some(backup)
+    }))
+}
+
+/// Replaces `path` with `backup`, once the backup has been read as a map
+/// and, if `verify` is set, written back correctly. The map it replaces is
+/// backed up in turn, keeping `keep` backups; that backup is returned.
+pub fn restore(path: &Path, backup: &Backup, keep: usize, verify: bool) -> Result<Option<PathBuf>> {
+    let data = fs::read(&backup.path).map_err(|e| {
+        PressorError::io(format!("File reading error {}", backup.path.display()), e)
+    })?;
+    let map = Map::from_bytes(&data)?;
+    let error = |e| PressorError::io(format!("File writing error in {}", path.display()), e);
+    let mut file = AtomicFile::create(path).map_err(error)?;
+    file.write_all(&data).map_err(error)?;
+    if verify {
+        verify::verify_file(file.temp_path(), path, map.json(), map.json())?;
+    }
+    let replaced = create(path, keep)?;
+    file.commit().map_err(error)?;
+    Ok(replaced)
+}
+
+/// Deletes the oldest backups of `path` beyond `keep`.
+pub fn prune(path: &Path, keep: usize) -> Result<()> {
+    let backups = list(path)?;
+    let excess = backups.len().saturating_sub(keep);
+    for backup in &backups[..excess] {
+        fs::remove_file(&backup.path).map_err(|e| {
+            PressorError::io(format!("Deleting backup {} failed", backup.path.display()), e)
+        })?;
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path, timestamp: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(INFIX);
+    name.push(timestamp);
+    path.with_file_name(name)
+}
+
+/// `2026-10-17T12-00-00`, optionally followed by `-N`.
+fn is_timestamp(text: &str) -> bool {
+    if text.len() < TIMESTAMP_LEN || !text.is_char_boundary(TIMESTAMP_LEN) {
+        return false;
+    }
+    let (time, repeat) = text.split_at(TIMESTAMP_LEN);
+    let shape = time.bytes().enumerate().all(|(i, b)| match i {
+        4 | 7 | 13 | 16 => b == b'-',
+        10 => b == b'T',
+        _ => b.is_ascii_digit(),
+    });
+    let repeat = repeat.is_empty()
+        || repeat
+            .strip_prefix('-')
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    shape && repeat
+}
+
+/// UTC time as `2026-10-17T12-00-00`, usable in file names everywhere.
+fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Year, month, day, hour, minute and second of `time` in UTC.
+pub(crate) fn utc(time: SystemTime) -> (i64, u32, u32, u64, u64, u64) {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    (year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+/// Gregorian date of a day counted from 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Empty directory for one test, removed again by the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pressor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn names_backups_by_utc_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_792_240_496);
+        assert_eq!(timestamp(time), "2026-10-17T12-34-56");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00-00-00");
+        assert_eq!(
+            backup_path(Path::new("maps/map.wbox"), "2026-10-17T12-34-56-2"),
+            Path::new("maps/map.wbox.bak.2026-10-17T12-34-56-2")
+        );
+    }
+
+    #[test]
+    fn civil_from_days_handles_leap_years() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        // 2000 is a leap year, 1900 and 2100 are not.
+        assert_eq!(civil_from_days(11_015), (2000, 2, 28));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-25_509), (1900, 2, 28));
+        assert_eq!(civil_from_days(-25_508), (1900, 3, 1));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(19_417), (2023, 3, 1));
+    }
+
+    #[test]
+    fn recognizes_timestamps() {
+        assert!(is_timestamp("2026-10-17T12-34-56"));
+        assert!(is_timestamp("2026-10-17T12-34-56-12"));
+        assert!(!is_timestamp("2026-10-17T12-34-56-"));
+        assert!(!is_timestamp("2026-10-17T12-34-5"));
+        assert!(!is_timestamp("2026-10-17 12-34-56"));
+        assert!(!is_timestamp("2026-10-17T12-34-56.tmp"));
+    }
+
+    #[test]
+    fn lists_backups_oldest_first() {
+        let dir = temp_dir("backup-list");
+        let map = dir.join("map.wbox");
+        for suffix in ["2026-10-17T12-00-00-10", "2026-10-17T12-00-00", "2025-01-01T00-00-00"] {
+            fs::write(backup_path(&map, suffix), suffix).unwrap();
+        }
+        fs::write(backup_path(&map, "2026-10-17T12-00-00-9"), "").unwrap();
+        fs::write(dir.join("other.wbox.bak.2026-10-17T12-00-00"), "").unwrap();
+        fs::write(dir.join("map.wbox.bak.notes"), "").unwrap();
+
+        let timestamps: Vec<_> = list(&map).unwrap().into_iter().map(|b| b.timestamp).collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            timestamps,
+            [
+                "2025-01-01T00-00-00",
+                "2026-10-17T12-00-00",
+                "2026-10-17T12-00-00-9",
+                "2026-10-17T12-00-00-10"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_newest_backups() {
+        let dir = temp_dir("backup-rotate");
+        let map = dir.join("map.wbox");
+        assert_eq!(create(&map, 3).unwrap(), None);
+        for version in 1..=5 {
+            fs::write(&map, format!("version {}", version)).unwrap();
+            assert!(create(&map, 3).unwrap().is_some());
+        }
+        assert_eq!(create(&map, 0).unwrap(), None);
+
+        let backups = list(&map).unwrap();
+        let contents: Vec<_> =
+            backups.iter().map(|b| fs::read_to_string(&b.path).unwrap()).collect();
+        prune(&map, 1).unwrap();
+        let left = list(&map).unwrap().len();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, ["version 3", "version 4", "version 5"]);
+        assert_eq!(left, 1);
+    }
+}
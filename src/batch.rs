@@ -0,0 +1,103 @@
+//! Finding the files of a batch conversion, which mirrors the folders
+//! they are in into an output folder.
+
+use crate::{PressorError, Result};
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A file found for batch processing, with its path relative to the
+/// folder that is mirrored in the output folder.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub input: PathBuf,
+    pub relative: PathBuf,
+}
+
+/// Finds the files to convert for `inputs`: glob patterns, files, and
+/// folders searched recursively for files with one of `extensions`.
+pub fn collect(inputs: &[String], extensions: &[&str]) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for input in inputs {
+        collect_jobs(input, extensions, &mut jobs)?;
+    }
+    Ok(jobs)
+}
+
+fn collect_jobs(input: &str, extensions: &[&str], jobs: &mut Vec<Job>) -> Result<()> {
+    if is_pattern(input) {
+        let base = pattern_base(input);
+        let paths = glob::glob(input).map_err(|e| {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, e);
+            PressorError::io(format!("Invalid pattern {}", input), error)
+        })?;
+        for path in paths {
+            let path = path.map_err(|e| {
+                let context = format!("Failed to read {}", e.path().display());
+                PressorError::io(context, e.into())
+            })?;
+            if path.is_file() {
+                let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+                jobs.push(Job { input: path, relative });
+            }
+        }
+        return Ok(());
+    }
+
+    let path = PathBuf::from(input);
+    if path.is_dir() {
+        walk_dir(&path, &path, extensions, jobs)
+    } else if path.is_file() {
+        let relative = PathBuf::from(path.file_name().unwrap_or_default());
+        jobs.push(Job { input: path, relative });
+        Ok(())
+    } else {
+        Err(PressorError::NotFound(path))
+    }
+}
+
+fn walk_dir(root: &Path, dir: &Path, extensions: &[&str], jobs: &mut Vec<Job>) -> Result<()> {
+    let error = |e| PressorError::io(format!("Failed to read directory {}", dir.display()), e);
+    let mut entries = fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+        .map_err(error)?;
+    entries.sort_by_key(|e| e.path());
+
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            walk_dir(root, &path, extensions, jobs)?;
+        } else if has_extension(&path, extensions) {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            jobs.push(Job { input: path, relative });
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase());
+    ext.is_some_and(|ext| extensions.contains(&ext.as_str()))
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// The leading part of a glob pattern that contains no wildcards.
+fn pattern_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if let Component::Normal(part) = component
+            && is_pattern(&part.to_string_lossy())
+        {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
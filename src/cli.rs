@@ -0,0 +1,699 @@
+use pressor::stream::STDIO;
+use anyhow::{bail, Result};
+use pressor::{backup, query::Query, render::RenderOptions, Indent, Level};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage:
+    pressor [FILE]
+    pressor compress <INPUT> -o <OUTPUT> [LEVEL] [LAYOUT] [WRITE] [--no-validate]
+    pressor decompress <INPUT> -o <OUTPUT> [LAYOUT] [WRITE]
+    pressor compress <INPUT>... --out-dir <DIR> [LEVEL] [LAYOUT] [WRITE] [--no-validate]
+    pressor decompress <INPUT>... --out-dir <DIR> [LAYOUT] [WRITE]
+    pressor recover <INPUT> -o <OUTPUT> [WRITE]
+    pressor verify-roundtrip <MAP>
+    pressor info <MAP> [--json]
+    pressor diff <OLD> <NEW> [--as-patch]
+    pressor merge <BASE> <OURS> <THEIRS> -o <OUTPUT> [WRITE]
+    pressor render <MAP> -o <PNG> [--borders] [--cities] [--palette <FILE>] [WRITE]
+    pressor laws <MAP> --list
+    pressor laws <MAP> --set <LAW>=<true|false>... -o <OUTPUT> [WRITE]
+    pressor query <MAP> <PATH> [--indent <N> | --tabs | --minify]
+    pressor patch <MAP> <PATCH> -o <OUTPUT> [WRITE]
+    pressor restore <MAP> --list
+    pressor restore <MAP> [<BACKUP>] [--backups <N>] [--no-verify]
+    pressor export-slot <SLOT> -o <DIR> [WRITE]
+    pressor import-slot <DIR> -o <SLOT> [WRITE]
+    pressor meta <SLOT> [--json]
+    pressor meta <SLOT> --update [--game-version <VERSION>]
+
+LEVEL:
+    --level <0-9>   zlib compression level (default 6)
+    --fast          same as --level 1
+    --best          same as --level 9
+    --smallest      try every level and keep the smallest output
+
+LAYOUT:
+    --indent <N>    indent JSON with N spaces (default 2 when decompressing)
+    --tabs          indent JSON with tabs
+    --minify        write JSON without any whitespace
+    --canonical     sort object keys so the output is byte-stable
+    --exact         only change whitespace: numbers, strings and key order
+                    are kept exactly as the game wrote them; this is the
+                    default unless --canonical is given
+
+WRITE:
+    --force         replace OUTPUT if it already exists
+    --in-place      allow OUTPUT to be the input file
+    --backups <N>   backups to keep of a replaced map (default 5, 0 for none)
+    --no-verify     do not read written maps back to check them
+
+compress keeps the JSON text as it is unless a LAYOUT option is given;
+--canonical alone writes minified JSON.
+
+The input of compress is checked to be valid JSON with map sections of
+the expected types first, since the game refuses to load a broken map;
+--force compresses it anyway. --no-validate does the same without allowing
+an existing OUTPUT to be replaced.
+
+Output files are written to a temporary file next to OUTPUT, synced to
+disk and then renamed over OUTPUT, so a failed write never leaves a
+half-written file behind. An existing OUTPUT is only replaced with --force,
+and an input file only with --in-place. The interactive mode asks instead.
+Once a new map has been written and checked, the map it replaces is
+copied to a backup next to it, such as map.wbox.bak.2026-10-17T12-00-00
+(UTC), and only the newest backups are kept. A failed command leaves the
+backups alone.
+
+A written map is read back, decompressed and compared with what was meant
+to be written before it replaces OUTPUT; if it differs, it is deleted and
+the command fails with exit code 13. --no-verify skips the check.
+
+Without a subcommand the interactive mode is started: the file is taken
+from the only argument or picked in a dialog, the direction is detected
+automatically and the output path is asked in a save dialog.
+
+A save slot folder of the game, such as saves/save1 holding map.wbox,
+map.meta and preview.png, may be given wherever a MAP, INPUT or OUTPUT
+file is expected; its map file is used then, except that decompress
+writes map.json into a folder given as OUTPUT. info also prints the
+metadata in map.meta and which of its values no longer match the map.
+
+With --out-dir every input may be a file, a directory (searched
+recursively) or a glob pattern such as saves/**/*.wbox; the directory
+tree below the pattern is mirrored in the output directory.
+
+An INPUT or OUTPUT of - means stdin or stdout; the map is then streamed
+instead of being loaded into memory. OUTPUT defaults to stdout when INPUT
+is -. A streamed compress only checks that the input is valid JSON, not
+the types of its map sections.
+
+verify-roundtrip checks that decompress followed by compress
+gives back the same JSON, number for number.
+
+recover decompresses as much of a damaged map as possible, closes the
+JSON where the data ends and reports which sections of the save were
+lost. OUTPUT is compressed when it ends in .wbox or .wbax.
+
+info prints the world size, save version and age, the number of actors
+of each species, buildings, cities, kingdoms, cultures, clans, wars and
+alliances, and how well the map compresses. --json prints the same as a
+JSON object.
+
+diff lists what changed between two maps: world laws, kingdoms and cities
+that were added or removed, the number of actors of each species, and
+changed tiles grouped in regions of 64 x 64 tiles. --as-patch prints a
+JSON Patch that turns OLD into NEW instead.
+
+merge combines the changes made to BASE in OURS and in THEIRS. World laws
+are merged by name, actors, kingdoms, cities and other lists of objects by
+id, tiles tile by tile and any other section as a whole. Where both sides
+changed the same thing differently, OURS is kept, the conflict is listed
+and the command fails after writing OUTPUT. OUTPUT is compressed when it
+ends in .wbox or .wbax.
+
+render draws the world as a PNG picture with one pixel per tile.
+--borders outlines the land of each kingdom and --cities marks every city.
+--palette reads tile colours from a JSON object such as
+{\"grass_low\": \"#5cac40\"}; tiles it does not list keep the built-in
+colours.
+
+laws --list prints the world laws of a map. --set switches laws on or off
+and may be repeated; a law is named as in the save, such as
+world_law_diplomacy, or without the world_law_ prefix. Names that are
+neither known laws nor in the map are refused. OUTPUT is compressed when it
+ends in .wbox or .wbax.
+
+query prints every value matched by a JSONPath such as mapStats.name,
+kingdoms[0], cities[*].name or actors_data[?(@.asset_id==\"human\")].
+Fields, indices (negative ones count from the end), * and filters
+comparing a field of @ with ==, !=, <, <=, > or >= are supported.
+
+patch applies PATCH, a JSON file holding either a JSON Patch (RFC 6902), a
+list of operations such as {\"op\": \"replace\", \"path\": \"/mapStats/name\",
+\"value\": \"Pangaea\"}, or a JSON Merge Patch (RFC 7396), an object merged
+into the map where null removes a field. A JSON Patch is applied entirely
+or not at all. OUTPUT is compressed when it ends in .wbox or .wbax.
+
+restore --list prints the backups of MAP, newest first. Without --list the
+newest backup replaces MAP, or the one given as its number in the list,
+its timestamp or its path. MAP itself is backed up first, so a restore
+can be undone as well.
+
+export-slot unpacks a save slot into DIR for editing: the map is
+decompressed to map.json, and map.meta and preview.png are copied.
+import-slot packs such a folder back into the save slot SLOT, compressing
+the map into map.wbox and updating map.meta from it. Both create the
+target folder if needed.
+
+meta prints the map.meta of a save slot, the summary the game shows in its
+load menu: name, description, world size, population, cities, kingdoms,
+save time and game version. Values that differ from the map are followed
+by the map's own. --update recomputes it from the map after editing and
+sets the save time to now; --game-version sets the game version as well.
+SLOT may also be a map file, which is then compared with the map.meta next
+to it. A compressed map.meta is written back compressed.
+
+Subcommands never open dialogs or wait for input and exit with a non-zero
+code on failure.
+
+Exit codes:
+    0   success
+    1   other failure
+    2   invalid command line
+    3   input file not found
+    4   unsupported file extension
+    5   file could not be read or written
+    10  compressed data is damaged
+    11  map is not valid UTF-8
+    12  map is not valid JSON
+    13  written map did not read back correctly
+    14  input is already compressed, or not compressed for decompress
+    20  file dialog was cancelled";
+
+pub enum Invocation {
+    Interactive(Option<PathBuf>),
+    Command(Command),
+    Help,
+}
+
+pub enum Command {
+    Convert(Convert),
+    Recover {
+        input: PathBuf,
+        output: PathBuf,
+        writing: Writing,
+    },
+    VerifyRoundTrip { input: PathBuf },
+    Info { input: PathBuf, json: bool },
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        as_patch: bool,
+    },
+    Merge {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+        output: PathBuf,
+        writing: Writing,
+    },
+    Render {
+        input: PathBuf,
+        output: PathBuf,
+        palette: Option<PathBuf>,
+        options: RenderOptions,
+        writing: Writing,
+    },
+    Laws {
+        input: PathBuf,
+        action: LawsAction,
+    },
+    Query {
+        input: PathBuf,
+        query: Query,
+        indent: Option<Indent>,
+    },
+    Patch {
+        input: PathBuf,
+        patch: PathBuf,
+        output: PathBuf,
+        writing: Writing,
+    },
+    Restore {
+        input: PathBuf,
+        action: RestoreAction,
+    },
+    ExportSlot {
+        slot: PathBuf,
+        output: PathBuf,
+        writing: Writing,
+    },
+    ImportSlot {
+        input: PathBuf,
+        slot: PathBuf,
+        writing: Writing,
+    },
+    Meta {
+        slot: PathBuf,
+        action: MetaAction,
+    },
+}
+
+pub enum MetaAction {
+    Show { json: bool },
+    Update { game_version: Option<String> },
+}
+
+pub enum LawsAction {
+    List,
+    Set {
+        changes: Vec<(String, bool)>,
+        output: PathBuf,
+        writing: Writing,
+    },
+}
+
+pub enum RestoreAction {
+    List,
+    Restore {
+        /// Number, timestamp or path of the backup; `None` for the newest.
+        backup: Option<String>,
+        /// Backups to keep, including the one of the replaced map.
+        keep: usize,
+        verify: bool,
+    },
+}
+
+/// How output files are written, set by the WRITE options.
+#[derive(Clone, Copy)]
+pub struct Writing {
+    /// Replace an output file that already exists.
+    pub force: bool,
+    /// Allow the output to be one of the input files.
+    pub in_place: bool,
+    /// Number of backups to keep of a replaced map.
+    pub backups: usize,
+    /// Read a written map back before it replaces the output.
+    pub verify: bool,
+}
+
+impl Default for Writing {
+    fn default() -> Self {
+        Self {
+            force: false,
+            in_place: false,
+            backups: backup::DEFAULT_KEEP,
+            verify: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Compress,
+    Decompress,
+}
+
+pub struct Convert {
+    pub direction: Direction,
+    pub inputs: Vec<String>,
+    pub target: Target,
+    pub options: Options,
+}
+
+/// Settings shared by every file of a conversion.
+#[derive(Default)]
+pub struct Options {
+    pub level: Level,
+    /// Compress input that is not a valid map, set by `--no-validate` or
+    /// `--force`.
+    pub skip_validation: bool,
+    pub writing: Writing,
+    /// Layout of written JSON; `None` keeps the default of the command.
+    pub indent: Option<Indent>,
+    /// Sort object keys at every level.
+    pub canonical: bool,
+}
+
+pub enum Target {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+pub fn parse(args: Vec<String>) -> Result<Invocation> {
+    let mut args = ArgList::new(args);
+    let Some(first) = args.next_word() else {
+        return Ok(Invocation::Interactive(None));
+    };
+
+    let command = match first.as_str() {
+        "-h" | "--help" | "help" => return Ok(Invocation::Help),
+        "compress" => Command::Convert(parse_convert(Direction::Compress, &mut args)?),
+        "decompress" => Command::Convert(parse_convert(Direction::Decompress, &mut args)?),
+        "recover" => {
+            let output = args.required_value(&["-o", "--output"])?;
+            let writing = parse_writing(&mut args)?;
+            let input = args.single_positional("INPUT")?;
+            Command::Recover {
+                input,
+                output,
+                writing,
+            }
+        }
+        "verify-roundtrip" => Command::VerifyRoundTrip {
+            input: args.single_positional("MAP")?,
+        },
+        "info" => {
+            let json = args.flag(&["--json"]);
+            let input = args.single_positional("MAP")?;
+            Command::Info { input, json }
+        }
+        "diff" => {
+            let as_patch = args.flag(&["--as-patch"]);
+            let [old, new] = args.fixed_positionals(["OLD", "NEW"])?;
+            Command::Diff { old, new, as_patch }
+        }
+        "merge" => {
+            let output = args.required_value(&["-o", "--output"])?;
+            let writing = parse_writing(&mut args)?;
+            let [base, ours, theirs] = args.fixed_positionals(["BASE", "OURS", "THEIRS"])?;
+            Command::Merge {
+                base,
+                ours,
+                theirs,
+                output,
+                writing,
+            }
+        }
+        "render" => {
+            let output = args.required_value(&["-o", "--output"])?;
+            let palette = args.value(&["--palette"])?.map(PathBuf::from);
+            let options = RenderOptions {
+                borders: args.flag(&["--borders"]),
+                cities: args.flag(&["--cities"]),
+            };
+            let writing = parse_writing(&mut args)?;
+            let input = args.single_positional("MAP")?;
+            Command::Render {
+                input,
+                output,
+                palette,
+                options,
+                writing,
+            }
+        }
+        "laws" => parse_laws(&mut args)?,
+        "query" => {
+            let indent = parse_indent(&mut args)?;
+            let [input, path] = args.fixed_positionals(["MAP", "PATH"])?;
+            let query = Query::parse(&path.to_string_lossy())?;
+            Command::Query {
+                input,
+                query,
+                indent,
+            }
+        }
+        "patch" => {
+            let output = args.required_value(&["-o", "--output"])?;
+            let writing = parse_writing(&mut args)?;
+            let [input, patch] = args.fixed_positionals(["MAP", "PATCH"])?;
+            Command::Patch {
+                input,
+                patch,
+                output,
+                writing,
+            }
+        }
+        "restore" => parse_restore(&mut args)?,
+        "export-slot" => {
+            let output = args.required_value(&["-o", "--output"])?;
+            let writing = parse_writing(&mut args)?;
+            let slot = args.single_positional("SLOT")?;
+            Command::ExportSlot {
+                slot,
+                output,
+                writing,
+            }
+        }
+        "import-slot" => {
+            let slot = args.required_value(&["-o", "--output"])?;
+            let writing = parse_writing(&mut args)?;
+            let input = args.single_positional("DIR")?;
+            Command::ImportSlot {
+                input,
+                slot,
+                writing,
+            }
+        }
+        "meta" => {
+            let json = args.flag(&["--json"]);
+            let update = args.flag(&["--update"]);
+            let game_version = args.value(&["--game-version"])?;
+            let slot = args.single_positional("SLOT")?;
+            let action = match (update, json, game_version) {
+                (true, false, game_version) => MetaAction::Update { game_version },
+                (true, true, _) => bail!("Options --update and --json cannot be used together"),
+                (false, _, Some(_)) => bail!("Option --game-version requires --update"),
+                (false, json, None) => MetaAction::Show { json },
+            };
+            Command::Meta { slot, action }
+        }
+        // A lone file argument, as when a map is dropped onto the program.
+        _ if args.args.is_empty() && !first.starts_with('-') => {
+            return Ok(Invocation::Interactive(Some(PathBuf::from(first))));
+        }
+        _ if first.starts_with('-') => bail!("Unknown option: {}", first),
+        _ => bail!("Unknown command: {}", first),
+    };
+
+    Ok(Invocation::Command(command))
+}
+
+fn parse_laws(args: &mut ArgList) -> Result<Command> {
+    let list = args.flag(&["--list"]);
+    let output = args.value(&["-o", "--output"])?;
+    let writing = parse_writing(args)?;
+    let mut changes = Vec::new();
+    while let Some(change) = args.value(&["--set"])? {
+        let (name, value) = match change.split_once('=') {
+            Some((name, "true")) => (name, true),
+            Some((name, "false")) => (name, false),
+            _ => bail!("Invalid law change {}, expected LAW=true or LAW=false", change),
+        };
+        changes.push((name.to_string(), value));
+    }
+    let input = args.single_positional("MAP")?;
+
+    let action = match (list, changes.is_empty(), output) {
+        (true, true, None) => LawsAction::List,
+        (false, false, Some(output)) => LawsAction::Set {
+            changes,
+            output: PathBuf::from(output),
+            writing,
+        },
+        (false, false, None) => bail!("Missing required option -o/--output"),
+        (true, false, _) => bail!("Options --list and --set cannot be used together"),
+        (_, true, _) => bail!("Use --list, or --set with -o"),
+    };
+    Ok(Command::Laws { input, action })
+}
+
+fn parse_restore(args: &mut ArgList) -> Result<Command> {
+    let list = args.flag(&["--list"]);
+    let keep = parse_backups(args)?;
+    let verify = !args.flag(&["--no-verify"]);
+    let mut positionals = args.positionals()?;
+    if positionals.len() > 2 {
+        bail!("Unexpected argument: {}", positionals[2]);
+    }
+    let backup = (positionals.len() == 2).then(|| positionals.remove(1));
+    let Some(input) = positionals.pop().map(PathBuf::from) else {
+        bail!("Missing MAP argument");
+    };
+
+    let action = match (list, backup) {
+        (true, None) => RestoreAction::List,
+        (true, Some(_)) => bail!("Option --list cannot be used with BACKUP"),
+        (false, backup) => RestoreAction::Restore {
+            backup,
+            keep,
+            verify,
+        },
+    };
+    Ok(Command::Restore { input, action })
+}
+
+fn parse_convert(direction: Direction, args: &mut ArgList) -> Result<Convert> {
+    let output = args.value(&["-o", "--output"])?;
+    let out_dir = args.value(&["--out-dir"])?;
+    let level = parse_level(args)?;
+    let skip_validation = args.flag(&["--no-validate"]);
+    let writing = parse_writing(args)?;
+    let indent = parse_indent(args)?;
+    let canonical = args.flag(&["--canonical"]);
+    let exact = args.flag(&["--exact"]);
+    if canonical && exact {
+        bail!("Options --canonical and --exact cannot be used together");
+    }
+    if direction == Direction::Decompress && level.is_some() {
+        bail!("Compression level options can only be used with compress");
+    }
+    if direction == Direction::Decompress && skip_validation {
+        bail!("Option --no-validate can only be used with compress");
+    }
+    let options = Options {
+        level: level.unwrap_or_default(),
+        // --force also stands for --no-validate, as it did before it
+        // allowed replacing files.
+        skip_validation: skip_validation || writing.force && direction == Direction::Compress,
+        writing,
+        indent,
+        canonical,
+    };
+    let inputs = args.positionals()?;
+    if inputs.is_empty() {
+        bail!("Missing INPUT argument");
+    }
+
+    let target = match (output, out_dir) {
+        (Some(_), Some(_)) => bail!("Options -o and --out-dir cannot be used together"),
+        (Some(output), None) => {
+            if inputs.len() > 1 {
+                bail!("Use --out-dir when converting several files");
+            }
+            Target::File(PathBuf::from(output))
+        }
+        (None, Some(_)) if inputs.iter().any(|i| i == STDIO) => {
+            bail!("Input - cannot be used with --out-dir")
+        }
+        (None, Some(dir)) => Target::Dir(PathBuf::from(dir)),
+        (None, None) if inputs == [STDIO] => Target::File(PathBuf::from(STDIO)),
+        (None, None) => bail!("Missing required option -o/--output or --out-dir"),
+    };
+
+    Ok(Convert {
+        direction,
+        inputs,
+        target,
+        options,
+    })
+}
+
+fn parse_writing(args: &mut ArgList) -> Result<Writing> {
+    Ok(Writing {
+        force: args.flag(&["--force"]),
+        in_place: args.flag(&["--in-place"]),
+        backups: parse_backups(args)?,
+        verify: !args.flag(&["--no-verify"]),
+    })
+}
+
+fn parse_backups(args: &mut ArgList) -> Result<usize> {
+    match args.value(&["--backups"])? {
+        Some(value) => match value.parse::<usize>() {
+            Ok(count) => Ok(count),
+            Err(_) => bail!("Invalid backup count {}, expected a number", value),
+        },
+        None => Ok(backup::DEFAULT_KEEP),
+    }
+}
+
+fn parse_level(args: &mut ArgList) -> Result<Option<Level>> {
+    let mut levels = Vec::new();
+    if let Some(value) = args.value(&["--level"])? {
+        match value.parse::<u32>() {
+            Ok(level) if level <= 9 => levels.push(Level::Fixed(level)),
+            _ => bail!("Invalid compression level {}, expected 0-9", value),
+        }
+    }
+    if args.flag(&["--fast"]) {
+        levels.push(Level::Fixed(1));
+    }
+    if args.flag(&["--best"]) {
+        levels.push(Level::Fixed(9));
+    }
+    if args.flag(&["--smallest"]) {
+        levels.push(Level::Smallest);
+    }
+
+    match levels.len() {
+        0 => Ok(None),
+        1 => Ok(levels.pop()),
+        _ => bail!("Only one of --level, --fast, --best and --smallest can be given"),
+    }
+}
+
+fn parse_indent(args: &mut ArgList) -> Result<Option<Indent>> {
+    let mut indents = Vec::new();
+    if let Some(value) = args.value(&["--indent"])? {
+        match value.parse::<usize>() {
+            Ok(width) => indents.push(Indent::Spaces(width)),
+            Err(_) => bail!("Invalid indent {}, expected a number of spaces", value),
+        }
+    }
+    if args.flag(&["--tabs"]) {
+        indents.push(Indent::Tabs);
+    }
+    if args.flag(&["--minify"]) {
+        indents.push(Indent::Minified);
+    }
+
+    match indents.len() {
+        0 => Ok(None),
+        1 => Ok(indents.pop()),
+        _ => bail!("Only one of --indent, --tabs and --minify can be given"),
+    }
+}
+
+/// Remaining command line arguments, consumed option by option.
+struct ArgList {
+    args: Vec<String>,
+}
+
+impl ArgList {
+    fn new(args: Vec<String>) -> Self {
+        Self { args }
+    }
+
+    fn next_word(&mut self) -> Option<String> {
+        if self.args.is_empty() {
+            None
+        } else {
+            Some(self.args.remove(0))
+        }
+    }
+
+    fn value(&mut self, names: &[&str]) -> Result<Option<String>> {
+        let Some(pos) = self.args.iter().position(|a| names.contains(&a.as_str())) else {
+            return Ok(None);
+        };
+        if pos + 1 >= self.args.len() {
+            bail!("Option {} requires a value", self.args[pos]);
+        }
+        let value = self.args.remove(pos + 1);
+        self.args.remove(pos);
+        Ok(Some(value))
+    }
+
+    fn required_value(&mut self, names: &[&str]) -> Result<PathBuf> {
+        match self.value(names)? {
+            Some(v) => Ok(PathBuf::from(v)),
+            None => bail!("Missing required option {}", names.join("/")),
+        }
+    }
+
+    fn flag(&mut self, names: &[&str]) -> bool {
+        let before = self.args.len();
+        self.args.retain(|a| !names.contains(&a.as_str()));
+        self.args.len() != before
+    }
+
+    fn positionals(&mut self) -> Result<Vec<String>> {
+        if let Some(unknown) = self.args.iter().find(|a| a.starts_with('-') && a.len() > 1) {
+            bail!("Unknown option: {}", unknown);
+        }
+        Ok(std::mem::take(&mut self.args))
+    }
+
+    fn single_positional(&mut self, name: &str) -> Result<PathBuf> {
+        let mut positionals = self.positionals()?;
+        match positionals.len() {
+            0 => bail!("Missing {} argument", name),
+            1 => Ok(PathBuf::from(positionals.remove(0))),
+            _ => bail!("Unexpected argument: {}", positionals[1]),
+        }
+    }
+
+    /// Exactly one positional argument for each of `names`.
+    fn fixed_positionals<const N: usize>(&mut self, names: [&str; N]) -> Result<[PathBuf; N]> {
+        let positionals = self.positionals()?;
+        if let Some(name) = names.get(positionals.len()) {
+            bail!("Missing {} argument", name);
+        }
+        if let Some(extra) = positionals.get(N) {
+            bail!("Unexpected argument: {}", extra);
+        }
+        Ok(std::array::from_fn(|i| PathBuf::from(&positionals[i])))
+    }
+}
@@ -0,0 +1,270 @@
+//! The compress and decompress commands, for single files, streams and
+//! batches.
+
+use crate::{
+    check_input,
+    cli::{Convert, Direction, Options, Target},
+    prepare_output, read_input, save_map,
+};
+use anyhow::{Context, Result};
+use pressor::{
+    batch,
+    format::{self, Format},
+    slot,
+    stream::{self, StreamOptions},
+    validate, Encoding, Level, Map, PressorError, WriteOptions,
+};
+use std::{fs, path::Path};
+
+/// Sizes in bytes of a converted file before and after conversion.
+struct Sizes {
+    input: u64,
+    output: u64,
+    /// Compression level used, when the file was compressed.
+    level: Option<u32>,
+}
+
+pub fn run(convert: Convert) -> Result<()> {
+    match &convert.target {
+        Target::Dir(out_dir) => run_batch(&convert, out_dir),
+        Target::File(output) => {
+            let input = slot::resolve_input(Path::new(&convert.inputs[0]));
+            let output = &match convert.direction {
+                // The game only loads compressed maps, so JSON never goes
+                // into the slot's map file.
+                Direction::Decompress if output.is_dir() => output.join(slot::JSON_FILE),
+                _ => slot::resolve_output(output),
+            };
+            if stream::is_stdio(&input) || stream::is_stdio(output) {
+                return convert_stream(convert.direction, &input, output, &convert.options);
+            }
+            check_input(&input)?;
+            prepare_output(&[&input], output, convert.options.writing)?;
+            convert_file(convert.direction, &input, output, &convert.options)
+        }
+    }
+}
+
+/// Converts every file matched by `inputs` into `out_dir` and prints a line
+/// per file plus a summary. Returns an error if any file failed.
+fn run_batch(convert: &Convert, out_dir: &Path) -> Result<()> {
+    let direction = convert.direction;
+    let (input_extensions, output_extension): (&[&str], _) = match direction {
+        Direction::Compress => (&["json"], "wbox"),
+        Direction::Decompress => (&["wbox", "wbax"], "json"),
+    };
+    let jobs = batch::collect(&convert.inputs, input_extensions)?;
+    if jobs.is_empty() {
+        return Err(anyhow::anyhow!("No matching files found"));
+    }
+
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut total_in = 0;
+    let mut total_out = 0;
+
+    for job in &jobs {
+        let output_path = out_dir.join(&job.relative).with_extension(output_extension);
+        let result = output_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .with_context(|| format!("Failed to create directory for {}", output_path.display()))
+            .and_then(|_| {
+                prepare_output(&[&job.input], &output_path, convert.options.writing)
+            })
+            .and_then(|_| match direction {
+                Direction::Compress => {
+                    compress_file(&job.input, &output_path, &convert.options)
+                }
+                Direction::Decompress => {
+                    decompress_file(&job.input, &output_path, &convert.options)
+                }
+            });
+
+        match result {
+            Ok(sizes) => {
+                succeeded += 1;
+                total_in += sizes.input;
+                total_out += sizes.output;
+                let level = sizes
+                    .level
+                    .map(|level| format!(", level {}", level))
+                    .unwrap_or_default();
+                println!(
+                    "▌ OK     {} -> {} ({} -> {} bytes{})",
+                    job.input.display(),
+                    output_path.display(),
+                    sizes.input,
+                    sizes.output,
+                    level
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                println!("▌ FAILED {}: {:#}", job.input.display(), e);
+            }
+        }
+    }
+
+    println!("\n▌ Files processed: {}", jobs.len());
+    println!("▌ Succeeded: {}, failed: {}", succeeded, failed);
+    println!("▌ Total size: {} bytes in, {} bytes out", total_in, total_out);
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} files failed", failed, jobs.len()));
+    }
+    Ok(())
+}
+
+/// Converts with [`stream::convert`], for a `-` input or output.
+fn convert_stream(
+    direction: Direction,
+    input: &Path,
+    output: &Path,
+    options: &Options,
+) -> Result<()> {
+    if !stream::is_stdio(input) {
+        check_input(input)?;
+    }
+    if !stream::is_stdio(output) {
+        prepare_output(&[input], output, options.writing)?;
+    }
+    let sizes = stream::convert(input, output, &stream_options(direction, options)?)
+        .map_err(|e| match e {
+            PressorError::InvalidJson(_) => anyhow::Error::from(e)
+                .context("Refusing to compress invalid JSON, use --force to compress anyway"),
+            e => e.into(),
+        })?;
+    if let Some(backup) = &sizes.backup {
+        println!("▌ Backup saved in: {}", backup.display());
+    }
+    match sizes.level {
+        Some(level) => eprintln!(
+            "▌ {} bytes in, {} bytes out, level {}",
+            sizes.input, sizes.output, level
+        ),
+        None => eprintln!("▌ {} bytes in, {} bytes out", sizes.input, sizes.output),
+    }
+    Ok(())
+}
+
+fn convert_file(
+    direction: Direction,
+    input_path: &Path,
+    output_path: &Path,
+    options: &Options,
+) -> Result<()> {
+    let data = read_input(input_path)?;
+    convert_data(direction, &data, output_path, options)
+}
+
+pub fn convert_data(
+    direction: Direction,
+    data: &[u8],
+    output_path: &Path,
+    options: &Options,
+) -> Result<()> {
+    let sizes = match direction {
+        Direction::Compress => compress_data(data, output_path, options)?,
+        Direction::Decompress => decompress_data(data, output_path, options)?,
+    };
+
+    let action = match direction {
+        Direction::Compress => "compress",
+        Direction::Decompress => "decompress",
+    };
+    println!("\n▌ File has been successfully {}ed!", action);
+    println!("▌ Original size: {} bytes", sizes.input);
+    println!("▌ Size after {}ing: {} bytes", action, sizes.output);
+    if let Some(level) = sizes.level {
+        println!("▌ Compression level: {}", level);
+    }
+    println!("▌ The result is saved in: {}", output_path.display());
+    Ok(())
+}
+
+fn decompress_file(input_path: &Path, output_path: &Path, options: &Options) -> Result<Sizes> {
+    decompress_data(&read_input(input_path)?, output_path, options)
+}
+
+fn decompress_data(compressed_data: &[u8], output_path: &Path, options: &Options) -> Result<Sizes> {
+    let format = format::detect_format(compressed_data);
+    if format == Format::PlainJson {
+        return Err(PressorError::WrongFormat(format).into());
+    }
+    // Data of an unknown format fails here with the decompression diagnostics.
+    let map = Map::from_bytes(compressed_data)?;
+    let written = save_map(&map, output_path, &write_options(options, Encoding::Json))?;
+
+    Ok(Sizes {
+        input: compressed_data.len() as u64,
+        output: written.size,
+        level: None,
+    })
+}
+
+fn compress_file(input_path: &Path, output_path: &Path, options: &Options) -> Result<Sizes> {
+    compress_data(&read_input(input_path)?, output_path, options)
+}
+
+fn compress_data(data: &[u8], output_path: &Path, options: &Options) -> Result<Sizes> {
+    let format = format::detect_format(data);
+    if format.is_compressed() {
+        return Err(PressorError::WrongFormat(format).into());
+    }
+    let map = match format {
+        // Text that does not start like JSON gets the JSON diagnostics.
+        Format::Unknown if format::looks_like_text(data) => Map::from_text(data)?,
+        _ => Map::from_bytes(data)?,
+    };
+    if !options.skip_validation {
+        validate::validate_map(map.json())
+            .map_err(PressorError::from)
+            .context("Refusing to compress an invalid map, use --force to compress anyway")?;
+    }
+    let written = save_map(&map, output_path, &write_options(options, Encoding::Compressed))?;
+
+    Ok(Sizes {
+        input: data.len() as u64,
+        output: written.size,
+        level: written.level,
+    })
+}
+
+/// Settings of a streamed conversion, refusing options that need the whole
+/// map.
+fn stream_options(direction: Direction, options: &Options) -> Result<StreamOptions> {
+    let level = match options.level {
+        Level::Fixed(level) => level,
+        Level::Smallest => {
+            return Err(anyhow::anyhow!("--smallest needs the whole map and cannot be streamed"));
+        }
+    };
+    if options.canonical {
+        return Err(anyhow::anyhow!("--canonical needs the whole map and cannot be streamed"));
+    }
+    Ok(StreamOptions {
+        encoding: match direction {
+            Direction::Compress => Encoding::Compressed,
+            Direction::Decompress => Encoding::Json,
+        },
+        level,
+        indent: options.indent,
+        validate: !options.skip_validation,
+        verify: options.writing.verify,
+        backups: options.writing.backups,
+    })
+}
+
+fn write_options(options: &Options, encoding: Encoding) -> WriteOptions {
+    WriteOptions {
+        encoding: Some(encoding),
+        level: options.level,
+        indent: options.indent,
+        canonical: options.canonical,
+        // Validation, when wanted, already happened with a better message.
+        validate: false,
+        verify: options.writing.verify,
+        backups: options.writing.backups,
+    }
+}
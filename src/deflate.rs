@@ -0,0 +1,43 @@
+//! zlib compression of map JSON.
+
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::{self, Write};
+
+/// zlib compression level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// A level from 0 (store only) to 9 (best).
+    Fixed(u32),
+    /// Try every level and keep the smallest output.
+    Smallest,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level::Fixed(6)
+    }
+}
+
+/// Compresses `text` into a zlib stream as the game writes it and returns
+/// the data together with the level used.
+pub fn compress(text: &str, level: Level) -> io::Result<(Vec<u8>, u32)> {
+    match level {
+        Level::Fixed(level) => Ok((compress_with_level(text, level)?, level)),
+        Level::Smallest => {
+            let mut best: Option<(Vec<u8>, u32)> = None;
+            for level in 0..=9 {
+                let data = compress_with_level(text, level)?;
+                if best.as_ref().is_none_or(|(smallest, _)| data.len() < smallest.len()) {
+                    best = Some((data, level));
+                }
+            }
+            Ok(best.expect("at least one level is tried"))
+        }
+    }
+}
+
+fn compress_with_level(text: &str, level: u32) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(text.as_bytes())?;
+    encoder.finish()
+}
@@ -0,0 +1,227 @@
+//! Differences between two saves in terms of what they contain.
+
+use crate::map::{EntityData, Id, SavedMap};
+use std::collections::BTreeMap;
+
+/// Side length in tiles of the square regions tile changes are grouped by.
+pub const REGION_SIZE: usize = 64;
+
+/// What changed from one map to another.
+#[derive(Debug, Default)]
+pub struct MapDiff {
+    pub laws: Vec<LawChange>,
+    pub kingdoms: EntityChanges,
+    pub cities: EntityChanges,
+    pub actors_before: usize,
+    pub actors_after: usize,
+    /// Species whose number of actors changed, by asset id.
+    pub species: Vec<CountChange>,
+    pub tiles: TileChanges,
+}
+
+impl MapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.laws.is_empty()
+            && self.kingdoms.is_empty()
+            && self.cities.is_empty()
+            && self.actors_before == self.actors_after
+            && self.species.is_empty()
+            && self.tiles.is_empty()
+    }
+}
+
+/// A law that was added, removed or switched. `None` means the law is not
+/// in that map.
+#[derive(Debug)]
+pub struct LawChange {
+    pub name: String,
+    pub before: Option<bool>,
+    pub after: Option<bool>,
+}
+
+/// Objects present in only one of the maps, matched by id, or by name for
+/// objects without an id.
+#[derive(Debug, Default)]
+pub struct EntityChanges {
+    pub added: Vec<Entity>,
+    pub removed: Vec<Entity>,
+}
+
+impl EntityChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub id: Option<Id>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CountChange {
+    pub name: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+#[derive(Debug, Default)]
+pub enum TileChanges {
+    /// Neither map has tile data, or both have identical tiles.
+    #[default]
+    None,
+    /// The worlds differ in size, so tiles are not compared.
+    Resized {
+        before: (usize, usize),
+        after: (usize, usize),
+    },
+    /// Regions with at least one changed tile, in row order.
+    Changed(Vec<RegionChange>),
+}
+
+impl TileChanges {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, TileChanges::None)
+    }
+}
+
+/// Changed tiles in the region whose top left tile is at `x`, `y`.
+#[derive(Debug)]
+pub struct RegionChange {
+    pub x: usize,
+    pub y: usize,
+    pub changed: usize,
+}
+
+/// Compares `before` with `after`.
+pub fn diff(before: &SavedMap, after: &SavedMap) -> MapDiff {
+    let actors_before = before.actors_data.as_deref().unwrap_or_default();
+    let actors_after = after.actors_data.as_deref().unwrap_or_default();
+    MapDiff {
+        laws: diff_laws(before, after),
+        kingdoms: diff_entities(&before.kingdoms, &after.kingdoms),
+        cities: diff_entities(&before.cities, &after.cities),
+        actors_before: actors_before.len(),
+        actors_after: actors_after.len(),
+        species: diff_counts(
+            actors_before.iter().map(|a| a.asset_id.as_deref()),
+            actors_after.iter().map(|a| a.asset_id.as_deref()),
+        ),
+        tiles: diff_tiles(before, after),
+    }
+}
+
+fn laws(map: &SavedMap) -> BTreeMap<&str, Option<bool>> {
+    map.world_laws
+        .iter()
+        .flat_map(|laws| &laws.list)
+        .map(|law| (law.name.as_str(), law.bool_val))
+        .collect()
+}
+
+fn diff_laws(before: &SavedMap, after: &SavedMap) -> Vec<LawChange> {
+    let before = laws(before);
+    let after = laws(after);
+    let mut names: Vec<&str> = before.keys().chain(after.keys()).copied().collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old = before.get(name);
+            let new = after.get(name);
+            (old != new).then(|| LawChange {
+                name: name.to_string(),
+                before: old.copied().flatten(),
+                after: new.copied().flatten(),
+            })
+        })
+        .collect()
+}
+
+/// Key an object is matched by across the two maps.
+fn entity_key(entity: &EntityData) -> (Option<&Id>, Option<&str>) {
+    match &entity.id {
+        Some(id) => (Some(id), None),
+        None => (None, entity.name.as_deref()),
+    }
+}
+
+fn diff_entities(
+    before: &Option<Vec<EntityData>>,
+    after: &Option<Vec<EntityData>>,
+) -> EntityChanges {
+    let before = before.as_deref().unwrap_or_default();
+    let after = after.as_deref().unwrap_or_default();
+    let only_in = |list: &[EntityData], other: &[EntityData]| -> Vec<Entity> {
+        list.iter()
+            .filter(|e| !other.iter().any(|o| entity_key(o) == entity_key(e)))
+            .map(|e| Entity {
+                id: e.id.clone(),
+                name: e.name.clone(),
+            })
+            .collect()
+    };
+    EntityChanges {
+        added: only_in(after, before),
+        removed: only_in(before, after),
+    }
+}
+
+fn diff_counts<'a>(
+    before: impl Iterator<Item = Option<&'a str>>,
+    after: impl Iterator<Item = Option<&'a str>>,
+) -> Vec<CountChange> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for name in before {
+        counts.entry(name.unwrap_or("unknown")).or_default().0 += 1;
+    }
+    for name in after {
+        counts.entry(name.unwrap_or("unknown")).or_default().1 += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(name, (before, after))| CountChange {
+            name: name.to_string(),
+            before,
+            after,
+        })
+        .collect()
+}
+
+fn diff_tiles(before: &SavedMap, after: &SavedMap) -> TileChanges {
+    let (Some(old), Some(new)) = (before.tiles(), after.tiles()) else {
+        return TileChanges::None;
+    };
+    let size = |rows: &[Vec<&str>]| (rows.iter().map(Vec::len).max().unwrap_or(0), rows.len());
+    if size(&old) != size(&new) || old.iter().zip(&new).any(|(a, b)| a.len() != b.len()) {
+        return TileChanges::Resized {
+            before: size(&old),
+            after: size(&new),
+        };
+    }
+
+    let mut regions: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (y, (old_row, new_row)) in old.iter().zip(&new).enumerate() {
+        for (x, (a, b)) in old_row.iter().zip(new_row).enumerate() {
+            if a != b {
+                *regions.entry((y / REGION_SIZE, x / REGION_SIZE)).or_default() += 1;
+            }
+        }
+    }
+    if regions.is_empty() {
+        return TileChanges::None;
+    }
+    TileChanges::Changed(
+        regions
+            .into_iter()
+            .map(|((row, column), changed)| RegionChange {
+                x: column * REGION_SIZE,
+                y: row * REGION_SIZE,
+                changed,
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,131 @@
+//! Errors returned by the library, each with a stable process exit code.
+
+use crate::{format::Format, inflate::DecompressError, validate::JsonError};
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T, E = PressorError> = std::result::Result<T, E>;
+
+/// Why reading, converting or writing a map failed.
+#[derive(Debug)]
+pub enum PressorError {
+    /// The file does not end in `.wbox`, `.wbax` or `.json`.
+    UnsupportedExtension(PathBuf),
+    /// The input file does not exist.
+    NotFound(PathBuf),
+    /// The compressed data is damaged or in an unknown format.
+    InvalidZlib(DecompressError),
+    /// The map text is not UTF-8; `offset` is the first bad byte.
+    InvalidUtf8 { offset: u64 },
+    /// The map is not valid JSON, or a section has the wrong type. The
+    /// error carries the line and column.
+    InvalidJson(JsonError),
+    /// Reading or writing a file failed.
+    Io { context: String, source: io::Error },
+    /// The input is already in the format it was to be converted to:
+    /// compressed data to compress, or plain JSON to decompress.
+    WrongFormat(Format),
+    /// A written file did not read back as what was written. The file was
+    /// discarded and any previous file at `path` left as it was.
+    VerifyFailed { path: PathBuf, reason: String },
+    /// The user closed a file dialog without choosing a file.
+    Cancelled,
+}
+
+impl PressorError {
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        PressorError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Exit code reported by the console tool for this error. The codes never
+    /// change, so scripts may rely on them:
+    ///
+    /// | code | error                  |
+    /// |------|------------------------|
+    /// | 3    | `NotFound`             |
+    /// | 4    | `UnsupportedExtension` |
+    /// | 5    | `Io`                   |
+    /// | 10   | `InvalidZlib`          |
+    /// | 11   | `InvalidUtf8`          |
+    /// | 12   | `InvalidJson`          |
+    /// | 13   | `VerifyFailed`         |
+    /// | 14   | `WrongFormat`          |
+    /// | 20   | `Cancelled`            |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            PressorError::NotFound(_) => 3,
+            PressorError::UnsupportedExtension(_) => 4,
+            PressorError::Io { .. } => 5,
+            PressorError::InvalidZlib(_) => 10,
+            PressorError::InvalidUtf8 { .. } => 11,
+            PressorError::InvalidJson(_) => 12,
+            PressorError::VerifyFailed { .. } => 13,
+            PressorError::WrongFormat(_) => 14,
+            PressorError::Cancelled => 20,
+        }
+    }
+}
+
+impl fmt::Display for PressorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PressorError::UnsupportedExtension(path) => write!(
+                f,
+                "Unsupported file extension: {:?}. Allowed extensions are .wbox, .wbax, .json",
+                path.extension().and_then(|e| e.to_str())
+            ),
+            PressorError::NotFound(path) => {
+                write!(f, "Input file does not exist: {}", path.display())
+            }
+            PressorError::InvalidZlib(e) => write!(f, "{}", e),
+            PressorError::InvalidUtf8 { offset } => {
+                write!(f, "Map is not valid UTF-8 text at byte {}", offset)
+            }
+            PressorError::InvalidJson(e) => write!(f, "{}", e),
+            PressorError::Io { context, .. } => f.write_str(context),
+            PressorError::WrongFormat(format) if format.is_compressed() => {
+                write!(f, "Input is already compressed ({})", format)
+            }
+            PressorError::WrongFormat(format) => write!(f, "Input is not compressed ({})", format),
+            PressorError::VerifyFailed { path, reason } => write!(
+                f,
+                "Verification of {} failed, the output was discarded: {}",
+                path.display(),
+                reason
+            ),
+            PressorError::Cancelled => f.write_str("Cancelled by the user"),
+        }
+    }
+}
+
+impl std::error::Error for PressorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PressorError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecompressError> for PressorError {
+    fn from(error: DecompressError) -> Self {
+        match error {
+            DecompressError::InvalidUtf8 { offset } => PressorError::InvalidUtf8 { offset },
+            error => PressorError::InvalidZlib(error),
+        }
+    }
+}
+
+impl From<JsonError> for PressorError {
+    fn from(error: JsonError) -> Self {
+        PressorError::InvalidJson(error)
+    }
+}
+
+impl From<serde_json::Error> for PressorError {
+    fn from(error: serde_json::Error) -> Self {
+        PressorError::InvalidJson(JsonError::from_serde(&error, None))
+    }
+}
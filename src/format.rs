@@ -0,0 +1,177 @@
+//! Detection of the container format of a map file.
+
+use flate2::read::DeflateDecoder;
+use std::{fmt, io::Read};
+
+/// Container format of a map file, detected from its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// zlib stream, as written by the game into `.wbox` files.
+    ZlibJson,
+    GzipJson,
+    /// Raw deflate stream without a zlib or gzip header.
+    DeflateJson,
+    PlainJson,
+    Unknown,
+}
+
+impl Format {
+    pub fn is_compressed(self) -> bool {
+        matches!(self, Format::ZlibJson | Format::GzipJson | Format::DeflateJson)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::ZlibJson => "zlib compressed JSON",
+            Format::GzipJson => "gzip compressed JSON",
+            Format::DeflateJson => "deflate compressed JSON",
+            Format::PlainJson => "plain JSON",
+            Format::Unknown => "unknown format",
+        })
+    }
+}
+
+/// Number of leading bytes `detect_format` looks at.
+pub const HEADER_LEN: usize = 4096;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Detects the format from the beginning of a file. Only the first
+/// `HEADER_LEN` bytes are inspected, so a prefix of the file is enough.
+pub fn detect_format(data: &[u8]) -> Format {
+    let header = &data[..data.len().min(HEADER_LEN)];
+
+    if is_zlib_header(header) {
+        Format::ZlibJson
+    } else if header.starts_with(&[0x1F, 0x8B, 0x08]) {
+        Format::GzipJson
+    } else if starts_like_json(header) {
+        Format::PlainJson
+    } else if inflates_to_json(header) {
+        Format::DeflateJson
+    } else {
+        Format::Unknown
+    }
+}
+
+/// Checks the CMF/FLG pair: deflate method, window size of at most 32K,
+/// no preset dictionary and a valid header checksum.
+fn is_zlib_header(header: &[u8]) -> bool {
+    let [cmf, flg, ..] = *header else {
+        return false;
+    };
+    cmf & 0x0F == 8
+        && cmf >> 4 <= 7
+        && flg & 0x20 == 0
+        && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0
+}
+
+pub fn strip_bom(data: &[u8]) -> &[u8] {
+    data.strip_prefix(UTF8_BOM).unwrap_or(data)
+}
+
+fn starts_like_json(header: &[u8]) -> bool {
+    matches!(
+        strip_bom(header).iter().find(|b| !b.is_ascii_whitespace()),
+        Some(b'{') | Some(b'[')
+    )
+}
+
+/// Whether the beginning of `data` is text rather than binary data.
+pub fn looks_like_text(data: &[u8]) -> bool {
+    let header = &data[..data.len().min(HEADER_LEN)];
+    let valid_utf8 = match std::str::from_utf8(header) {
+        Ok(_) => true,
+        // A multi-byte character may be cut at the end of the header.
+        Err(e) => e.error_len().is_none(),
+    };
+    valid_utf8
+        && !header
+            .iter()
+            .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r'))
+}
+
+fn inflates_to_json(header: &[u8]) -> bool {
+    let mut prefix = [0u8; 64];
+    let mut decoder = DeflateDecoder::new(header);
+    let mut len = 0;
+    while len < prefix.len() {
+        match decoder.read(&mut prefix[len..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
+        }
+    }
+    len > 0 && starts_like_json(&prefix[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    const JSON: &[u8] = br#"{"saveVersion":15,"width":64}"#;
+
+    #[test]
+    fn detects_compressed_formats() {
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(JSON).unwrap();
+        assert_eq!(detect_format(&zlib.finish().unwrap()), Format::ZlibJson);
+        // Any level is recognized, the header only has to be consistent.
+        let mut fast = ZlibEncoder::new(Vec::new(), Compression::fast());
+        fast.write_all(JSON).unwrap();
+        assert_eq!(detect_format(&fast.finish().unwrap()), Format::ZlibJson);
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(JSON).unwrap();
+        assert_eq!(detect_format(&gzip.finish().unwrap()), Format::GzipJson);
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(JSON).unwrap();
+        assert_eq!(detect_format(&deflate.finish().unwrap()), Format::DeflateJson);
+    }
+
+    #[test]
+    fn detects_plain_json() {
+        assert_eq!(detect_format(JSON), Format::PlainJson);
+        assert_eq!(detect_format(b" \r\n\t[1]"), Format::PlainJson);
+        assert_eq!(detect_format(b"\xEF\xBB\xBF{}"), Format::PlainJson);
+        assert_eq!(strip_bom(b"\xEF\xBB\xBF{}"), b"{}");
+        assert_eq!(strip_bom(b"{}"), b"{}");
+    }
+
+    #[test]
+    fn rejects_unknown_data() {
+        assert_eq!(detect_format(b""), Format::Unknown);
+        assert_eq!(detect_format(b"hello"), Format::Unknown);
+        assert_eq!(detect_format(b"\x89PNG\r\n\x1a\n"), Format::Unknown);
+        // A zlib header with a wrong checksum, or a preset dictionary.
+        assert_eq!(detect_format(&[0x78, 0x9d, 0, 0]), Format::Unknown);
+        assert_eq!(detect_format(&[0x78, 0xbb, 0, 0]), Format::Unknown);
+        assert!(!detect_format(b"hello").is_compressed());
+        assert!(Format::DeflateJson.is_compressed());
+    }
+
+    #[test]
+    fn looks_only_at_the_header() {
+        let mut data = JSON.to_vec();
+        data.resize(HEADER_LEN, b' ');
+        data.extend_from_slice(b"\0\0\0");
+        assert_eq!(detect_format(&data), Format::PlainJson);
+        assert!(looks_like_text(&data));
+    }
+
+    #[test]
+    fn tells_text_from_binary() {
+        assert!(looks_like_text("été\tà\r\n".as_bytes()));
+        assert!(!looks_like_text(b"ab\0c"));
+        assert!(!looks_like_text(b"\xff\xfe"));
+        // A character cut at the end of the header is still text.
+        let mut data = vec![b'a'; HEADER_LEN - 1];
+        data.extend_from_slice("é".as_bytes());
+        assert!(looks_like_text(&data));
+    }
+}
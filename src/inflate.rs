@@ -0,0 +1,521 @@
+//! Decompression with detailed diagnostics for damaged maps.
+
+use crate::format::Format;
+use flate2::{bufread::MultiGzDecoder, Decompress, FlushDecompress, Status};
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+};
+
+/// Why a compressed map could not be decompressed.
+#[derive(Debug)]
+pub enum DecompressError {
+    /// The data does not start with a header of a supported format.
+    InvalidHeader(String),
+    /// The deflate stream contains invalid data at `offset`.
+    Corrupt { offset: u64, message: String },
+    /// The input ended at `offset` before the end of the stream.
+    Truncated { offset: u64 },
+    /// The stream decompressed fully but its Adler-32 trailer does not match.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The decompressed payload is not UTF-8; `offset` is the first bad byte.
+    InvalidUtf8 { offset: u64 },
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompressError::InvalidHeader(reason) => write!(f, "Invalid header: {}", reason),
+            DecompressError::Corrupt { offset, message } => {
+                write!(f, "Corrupt compressed data at byte {}: {}", offset, message)
+            }
+            DecompressError::Truncated { offset } => write!(
+                f,
+                "Compressed stream is truncated, input ended at byte {}",
+                offset
+            ),
+            DecompressError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Adler-32 checksum mismatch: stored {:08x}, computed {:08x}",
+                expected, actual
+            ),
+            DecompressError::InvalidUtf8 { offset } => write!(
+                f,
+                "Decompressed data is not valid UTF-8 at byte {}",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+/// Decompresses a map and checks that the result is UTF-8 text.
+pub fn decompress(data: &[u8], format: Format) -> Result<String, DecompressError> {
+    let mut out = Vec::new();
+    inflate(data, format, &mut out)?;
+    String::from_utf8(out).map_err(|e| DecompressError::InvalidUtf8 {
+        offset: e.utf8_error().valid_up_to() as u64,
+    })
+}
+
+/// Decompresses `data` into `out`. On error `out` keeps everything that
+/// was decompressed before the failure.
+pub fn inflate(data: &[u8], format: Format, out: &mut Vec<u8>) -> Result<(), DecompressError> {
+    match format {
+        Format::ZlibJson => inflate_zlib(data, out),
+        Format::DeflateJson => inflate_raw(data, 0, out).map(|_| ()),
+        Format::GzipJson => {
+            let mut input = Position::new(data);
+            inflate_gzip(&mut input, out).map_err(|damage| match damage {
+                Damage::Data(error) => error,
+                // Reading a slice into a vector does not fail otherwise.
+                Damage::Io(error) => DecompressError::Corrupt {
+                    offset: input.offset,
+                    message: error.to_string(),
+                },
+            })
+        }
+        // Lets a zlib stream with a damaged header report what is wrong with it.
+        Format::Unknown if data.first().is_some_and(|cmf| cmf & 0x0F == 8) => {
+            inflate_zlib(data, out)
+        }
+        Format::PlainJson | Format::Unknown => Err(DecompressError::InvalidHeader(format!(
+            "data is {}, not a zlib, gzip or deflate stream",
+            format
+        ))),
+    }
+}
+
+/// Decompresses `input` into `output` as it is read, for maps too large to
+/// hold in memory. Damage is reported as an [`io::ErrorKind::InvalidData`]
+/// error wrapping the [`DecompressError`] that [`inflate`] would give; any
+/// other error comes from reading or writing.
+pub fn inflate_reader<R: BufRead, W: Write>(
+    input: R,
+    format: Format,
+    mut output: W,
+) -> io::Result<()> {
+    let mut input = Position::new(input);
+    let is_zlib = match format {
+        Format::ZlibJson => true,
+        Format::Unknown => input.fill_buf()?.first().is_some_and(|cmf| cmf & 0x0F == 8),
+        _ => false,
+    };
+    let result = match format {
+        _ if is_zlib => inflate_zlib_reader(&mut input, &mut output),
+        Format::DeflateJson => inflate_raw_reader(&mut input, &mut output, None),
+        Format::GzipJson => inflate_gzip(&mut input, &mut output),
+        _ => Err(DecompressError::InvalidHeader(format!(
+            "data is {}, not a zlib, gzip or deflate stream",
+            format
+        ))
+        .into()),
+    };
+    result.map_err(|damage| match damage {
+        Damage::Data(error) => io::Error::new(io::ErrorKind::InvalidData, error),
+        Damage::Io(error) => error,
+    })
+}
+
+/// Error of [`inflate_reader`] while it runs: either the data is damaged,
+/// or reading or writing failed.
+enum Damage {
+    Data(DecompressError),
+    Io(io::Error),
+}
+
+impl From<DecompressError> for Damage {
+    fn from(error: DecompressError) -> Self {
+        Damage::Data(error)
+    }
+}
+
+impl From<io::Error> for Damage {
+    fn from(error: io::Error) -> Self {
+        Damage::Io(error)
+    }
+}
+
+fn inflate_zlib_reader<R: BufRead>(
+    input: &mut Position<R>,
+    output: &mut impl Write,
+) -> Result<(), Damage> {
+    let mut header = [0; 2];
+    read_exact(input, &mut header)?;
+    check_zlib_header(header[0], header[1])?;
+
+    let mut checksum = Adler32::new();
+    inflate_raw_reader(input, output, Some(&mut checksum))?;
+    let mut trailer = [0; 4];
+    read_exact(input, &mut trailer)?;
+
+    let expected = u32::from_be_bytes(trailer);
+    let actual = checksum.finish();
+    if expected != actual {
+        return Err(DecompressError::ChecksumMismatch { expected, actual }.into());
+    }
+    Ok(())
+}
+
+/// Reads exactly `buf.len()` bytes, reporting the end of the input as a
+/// truncated stream.
+fn read_exact<R: BufRead>(input: &mut Position<R>, buf: &mut [u8]) -> Result<(), Damage> {
+    match input.read_exact(buf) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            Err(DecompressError::Truncated {
+                offset: input.offset,
+            }
+            .into())
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Inflates a raw deflate stream from `input`, leaving the bytes after it
+/// unread. `checksum`, if any, is updated with the decompressed data.
+fn inflate_raw_reader<R: BufRead>(
+    input: &mut Position<R>,
+    output: &mut impl Write,
+    mut checksum: Option<&mut Adler32>,
+) -> Result<(), Damage> {
+    let mut inflater = Decompress::new(false);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let data = input.fill_buf()?;
+        let at_end = data.is_empty();
+        let (consumed, produced) = (inflater.total_in(), inflater.total_out());
+        let status = inflater.decompress(data, &mut buf, FlushDecompress::None);
+        input.consume((inflater.total_in() - consumed) as usize);
+        let status = status.map_err(|e| DecompressError::Corrupt {
+            offset: input.offset,
+            message: e.to_string(),
+        })?;
+
+        let written = &buf[..(inflater.total_out() - produced) as usize];
+        if let Some(checksum) = checksum.as_deref_mut() {
+            checksum.update(written);
+        }
+        output.write_all(written)?;
+        match status {
+            Status::StreamEnd => return Ok(()),
+            _ if at_end && written.is_empty() => {
+                return Err(DecompressError::Truncated {
+                    offset: input.offset,
+                }
+                .into());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn inflate_zlib(data: &[u8], out: &mut Vec<u8>) -> Result<(), DecompressError> {
+    let [cmf, flg, ..] = *data else {
+        return Err(DecompressError::Truncated {
+            offset: data.len() as u64,
+        });
+    };
+    check_zlib_header(cmf, flg)?;
+
+    let start = out.len();
+    let end = 2 + inflate_raw(&data[2..], 2, out)?;
+    let Some(trailer) = data.get(end..end + 4) else {
+        return Err(DecompressError::Truncated {
+            offset: data.len() as u64,
+        });
+    };
+
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let actual = adler32(&out[start..]);
+    if expected != actual {
+        return Err(DecompressError::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+fn check_zlib_header(cmf: u8, flg: u8) -> Result<(), DecompressError> {
+    if cmf & 0x0F != 8 {
+        return Err(DecompressError::InvalidHeader(format!(
+            "compression method {} is not deflate",
+            cmf & 0x0F
+        )));
+    }
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(DecompressError::InvalidHeader(
+            "zlib header checksum is wrong".to_string(),
+        ));
+    }
+    if flg & 0x20 != 0 {
+        return Err(DecompressError::InvalidHeader(
+            "preset dictionaries are not supported".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Inflates a raw deflate stream and returns the number of input bytes it
+/// took. `base` is the position of `data` in the file, used in errors.
+fn inflate_raw(data: &[u8], base: u64, out: &mut Vec<u8>) -> Result<usize, DecompressError> {
+    let mut inflater = Decompress::new(false);
+    loop {
+        if out.capacity() - out.len() < 32 * 1024 {
+            out.reserve(64 * 1024);
+        }
+        let consumed = inflater.total_in() as usize;
+        let produced = inflater.total_out();
+        let status = inflater
+            .decompress_vec(&data[consumed..], out, FlushDecompress::None)
+            .map_err(|e| DecompressError::Corrupt {
+                offset: base + inflater.total_in(),
+                message: e.to_string(),
+            })?;
+
+        match status {
+            Status::StreamEnd => return Ok(inflater.total_in() as usize),
+            Status::Ok | Status::BufError => {
+                if inflater.total_in() as usize == consumed && inflater.total_out() == produced {
+                    return Err(DecompressError::Truncated {
+                        offset: base + inflater.total_in(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn inflate_gzip<R: BufRead>(
+    input: &mut Position<R>,
+    output: &mut impl Write,
+) -> Result<(), Damage> {
+    let mut decoder = MultiGzDecoder::new(input);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match decoder.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(DecompressError::Truncated {
+                    offset: decoder.get_ref().offset,
+                }
+                .into());
+            }
+            Err(e)
+                if matches!(e.kind(), io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData) =>
+            {
+                return Err(DecompressError::Corrupt {
+                    offset: decoder.get_ref().offset,
+                    message: e.to_string(),
+                }
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        output.write_all(&buf[..n])?;
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut checksum = Adler32::new();
+    checksum.update(data);
+    checksum.finish()
+}
+
+/// Running Adler-32 checksum, as stored in a zlib trailer.
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MOD: u32 = 65521;
+
+    fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        // 5552 is the largest block for which `b` cannot overflow before reducing.
+        for chunk in data.chunks(5552) {
+            for &byte in chunk {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+            self.a %= Self::MOD;
+            self.b %= Self::MOD;
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Buffered input that counts the bytes taken from it, so that errors can
+/// name the exact offset rather than how far the buffer read ahead.
+struct Position<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R> Position<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, offset: 0 }
+    }
+}
+
+impl<R: BufRead> Read for Position<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Position<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.inner.consume(amount);
+        self.offset += amount as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+
+    const JSON: &str = r#"{"saveVersion":15,"mapStats":{"name":"Été"}}"#;
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Decompresses `data` both in memory and as a stream, checking that
+    /// the two agree.
+    fn inflate_both(data: &[u8], format: Format) -> Result<Vec<u8>, DecompressError> {
+        let mut whole = Vec::new();
+        let result = inflate(data, format, &mut whole);
+        let mut streamed = Vec::new();
+        let stream_result = inflate_reader(data, format, &mut streamed)
+            .map_err(|e| *e.into_inner().unwrap().downcast::<DecompressError>().unwrap());
+        assert_eq!(streamed, whole);
+        assert_eq!(format!("{:?}", stream_result), format!("{:?}", result));
+        result.map(|_| whole)
+    }
+
+    #[test]
+    fn decompresses_a_valid_stream() {
+        let data = zlib(JSON.as_bytes());
+        assert_eq!(decompress(&data, Format::ZlibJson).unwrap(), JSON);
+        assert_eq!(inflate_both(&data, Format::ZlibJson).unwrap(), JSON.as_bytes());
+    }
+
+    #[test]
+    fn reports_where_a_truncated_stream_ends() {
+        let data = zlib(JSON.as_bytes());
+        for len in [0, 1, 5, data.len() - 4, data.len() - 1] {
+            let error = inflate_both(&data[..len], Format::ZlibJson).unwrap_err();
+            assert!(
+                matches!(error, DecompressError::Truncated { offset } if offset == len as u64),
+                "{}: {:?}",
+                len,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_bad_header() {
+        let data = zlib(JSON.as_bytes());
+        let header = |cmf, flg| {
+            let mut data = data.clone();
+            data[..2].copy_from_slice(&[cmf, flg]);
+            inflate_both(&data, Format::ZlibJson).unwrap_err().to_string()
+        };
+        assert_eq!(header(0x78, 0x9d), "Invalid header: zlib header checksum is wrong");
+        assert_eq!(
+            header(0x77, 0x85),
+            "Invalid header: compression method 7 is not deflate"
+        );
+        assert_eq!(
+            header(0x78, 0xbb),
+            "Invalid header: preset dictionaries are not supported"
+        );
+        let error = inflate_both(JSON.as_bytes(), Format::Unknown).unwrap_err();
+        assert!(matches!(error, DecompressError::InvalidHeader(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn reports_a_checksum_mismatch() {
+        let mut data = zlib(JSON.as_bytes());
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let actual = adler32(JSON.as_bytes());
+        match inflate_both(&data, Format::ZlibJson).unwrap_err() {
+            DecompressError::ChecksumMismatch { expected, actual: computed } => {
+                assert_eq!(expected, actual ^ 1);
+                assert_eq!(computed, actual);
+            }
+            error => panic!("{:?}", error),
+        }
+    }
+
+    #[test]
+    fn ignores_data_after_the_stream() {
+        let mut data = zlib(JSON.as_bytes());
+        data.extend_from_slice(b"trailing");
+        assert_eq!(inflate_both(&data, Format::ZlibJson).unwrap(), JSON.as_bytes());
+    }
+
+    #[test]
+    fn reports_the_offset_of_corrupt_data() {
+        // A final block of the reserved type 3 right after the header.
+        let error = inflate_both(&[0x78, 0x9c, 0x07, 0x00], Format::ZlibJson).unwrap_err();
+        assert!(
+            matches!(error, DecompressError::Corrupt { offset: 3, .. }),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn reports_invalid_utf8() {
+        let data = zlib(b"{\"name\":\"\xff\"}");
+        match decompress(&data, Format::ZlibJson).unwrap_err() {
+            DecompressError::InvalidUtf8 { offset } => assert_eq!(offset, 9),
+            error => panic!("{:?}", error),
+        }
+    }
+
+    #[test]
+    fn decompresses_raw_deflate_and_gzip() {
+        let raw = zlib(JSON.as_bytes());
+        let raw = &raw[2..raw.len() - 4];
+        assert_eq!(inflate_both(raw, Format::DeflateJson).unwrap(), JSON.as_bytes());
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(JSON.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(inflate_both(&gzip, Format::GzipJson).unwrap(), JSON.as_bytes());
+        let error = inflate_both(&gzip[..gzip.len() - 3], Format::GzipJson).unwrap_err();
+        assert!(matches!(error, DecompressError::Truncated { .. }), "{:?}", error);
+    }
+
+    #[test]
+    fn adler32_matches_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        let long = vec![0xff; 100_000];
+        let mut running = Adler32::new();
+        for chunk in long.chunks(777) {
+            running.update(chunk);
+        }
+        assert_eq!(running.finish(), adler32(&long));
+    }
+}
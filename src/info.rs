@@ -0,0 +1,166 @@
+//! Summary of what a save contains.
+
+use crate::{
+    deflate::{self, Level},
+    map::SavedMap,
+    meta::MapMeta,
+    Map, PressorError, Result,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// World time the game counts as one year: twelve months of five seconds.
+///
+/// The game keeps this in its `Date` class, as `MONTHS_IN_YEAR = 12` and
+/// `SECONDS_IN_MONTH = 5`, and divides `mapStats.worldTime` by their product
+/// for the year shown in the world info window.
+pub const SECONDS_PER_YEAR: f64 = 60.0;
+
+/// Statistics of a map, as printed by `pressor info`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MapInfo {
+    pub name: Option<String>,
+    pub save_version: Option<i64>,
+    /// World size as stored in the save.
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    /// World size in tiles, counted from the tile data.
+    pub tiles: Option<TileSize>,
+    /// World time in seconds.
+    pub world_time: Option<f64>,
+    /// Full years since the world was created.
+    pub world_age: Option<u64>,
+    pub actors: usize,
+    /// Number of actors of each species.
+    pub species: BTreeMap<String, usize>,
+    pub buildings: usize,
+    pub cities: usize,
+    pub kingdoms: usize,
+    pub cultures: usize,
+    pub clans: usize,
+    pub wars: usize,
+    pub alliances: usize,
+    pub compressed_size: u64,
+    /// True when the map was read as JSON and `compressed_size` is what
+    /// compressing it with the default level gives.
+    pub compressed_estimated: bool,
+    pub decompressed_size: u64,
+    /// Decompressed size divided by compressed size.
+    pub ratio: f64,
+    /// Contents of `map.meta` when the map was read from a save slot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<MapMeta>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TileSize {
+    pub width: u64,
+    pub height: u64,
+}
+
+impl MapInfo {
+    /// Collects the statistics of `map`, which was read from a file of
+    /// `file_size` bytes.
+    pub fn new(map: &Map, file_size: u64) -> Result<Self> {
+        let saved = map.to_saved_map()?;
+        let decompressed_size = map.json().len() as u64;
+        let (compressed_size, compressed_estimated) = if map.format().is_compressed() {
+            (file_size, false)
+        } else {
+            let (data, _) = deflate::compress(map.json(), Level::default())
+                .map_err(|e| PressorError::io("Compression error", e))?;
+            (data.len() as u64, true)
+        };
+
+        let stats = saved.map_stats.as_ref();
+        let world_time = stats.and_then(|stats| stats.world_time);
+        let actors = saved.actors_data.as_deref().unwrap_or_default();
+        let mut species = BTreeMap::new();
+        for actor in actors {
+            let asset = actor.asset_id.as_deref().unwrap_or("unknown");
+            *species.entry(asset.to_string()).or_insert(0) += 1;
+        }
+
+        Ok(Self {
+            name: stats.and_then(|stats| stats.name.clone()),
+            save_version: saved.save_version,
+            width: saved.width,
+            height: saved.height,
+            tiles: tile_size(&saved),
+            world_time,
+            world_age: world_time.map(|time| (time / SECONDS_PER_YEAR).max(0.0) as u64),
+            actors: actors.len(),
+            species,
+            buildings: count(&saved.buildings),
+            cities: count(&saved.cities),
+            kingdoms: count(&saved.kingdoms),
+            cultures: count(&saved.cultures),
+            clans: count(&saved.clans),
+            wars: count(&saved.wars),
+            alliances: count(&saved.alliances),
+            compressed_size,
+            compressed_estimated,
+            decompressed_size,
+            ratio: decompressed_size as f64 / compressed_size.max(1) as f64,
+            meta: None,
+        })
+    }
+}
+
+fn count<T>(section: &Option<Vec<T>>) -> usize {
+    section.as_ref().map_or(0, Vec::len)
+}
+
+/// Rows of `tileAmounts` and the number of tiles in the first of them.
+fn tile_size(map: &SavedMap) -> Option<TileSize> {
+    let rows = map.tile_amounts.as_ref()?;
+    let width = rows.first()?.iter().map(|&n| n.max(0) as u64).sum();
+    Some(TileSize {
+        width,
+        height: rows.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map_info(map: serde_json::Value) -> MapInfo {
+        let map = Map::from_value(&map).unwrap();
+        let size = map.json().len() as u64;
+        MapInfo::new(&map, size).unwrap()
+    }
+
+    #[test]
+    fn world_age_counts_full_years() {
+        // A world saved in the game at year 100, month 6.
+        let info = map_info(json!({ "mapStats": { "worldTime": 6025.5 } }));
+        assert_eq!(info.world_time, Some(6025.5));
+        assert_eq!(info.world_age, Some(100));
+
+        let info = map_info(json!({ "mapStats": { "worldTime": 59.9 } }));
+        assert_eq!(info.world_age, Some(0));
+        let info = map_info(json!({ "mapStats": { "worldTime": -5.0 } }));
+        assert_eq!(info.world_age, Some(0));
+        let info = map_info(json!({ "mapStats": {} }));
+        assert_eq!(info.world_age, None);
+    }
+
+    #[test]
+    fn counts_tiles_and_species() {
+        let info = map_info(json!({
+            "width": 2,
+            "height": 1,
+            "tileAmounts": [[3, 5], [8]],
+            "actors_data": [{ "asset_id": "human" }, { "asset_id": "human" }, {}]
+        }));
+        assert_eq!(info.width, Some(2));
+        let tiles = info.tiles.unwrap();
+        assert_eq!((tiles.width, tiles.height), (8, 2));
+        assert_eq!(info.actors, 3);
+        assert_eq!(info.species.get("human"), Some(&2));
+        assert_eq!(info.species.get("unknown"), Some(&1));
+        assert!(info.compressed_estimated);
+    }
+}
@@ -0,0 +1,255 @@
+//! Layout of written JSON: indentation, minification and key order.
+
+use serde::Serialize;
+use serde_json::{from_str, ser::PrettyFormatter, Serializer, Value};
+use std::io::{self, Write};
+
+/// Indentation of written JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+    /// No whitespace at all.
+    Minified,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+/// Re-serializes JSON with the given layout, optionally with sorted keys.
+/// Text that does not parse is returned unchanged.
+pub fn format_json(json_str: &str, indent: Indent, canonical: bool) -> String {
+    match from_str::<Value>(json_str) {
+        Ok(mut parsed) => {
+            if canonical {
+                parsed.sort_all_objects();
+            }
+            to_string_with_indent(&parsed, indent).unwrap_or_else(|_| json_str.to_string())
+        }
+        Err(_) => json_str.to_string(),
+    }
+}
+
+/// Serializes `value` with the given indentation.
+pub fn to_string_with_indent(value: &Value, indent: Indent) -> serde_json::Result<String> {
+    let indent = match indent {
+        Indent::Minified => return serde_json::to_string(value),
+        Indent::Spaces(width) => " ".repeat(width),
+        Indent::Tabs => "\t".to_string(),
+    };
+    let mut out = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+    value.serialize(&mut serializer)?;
+    Ok(String::from_utf8(out).expect("serde_json writes UTF-8"))
+}
+
+/// Re-indents JSON text without parsing it into a `Value`, so numbers,
+/// strings and key order stay exactly as written.
+pub fn reindent(json_str: &str, indent: Indent) -> String {
+    let mut out = Vec::with_capacity(json_str.len());
+    let mut pretty = PrettyWriter::new(&mut out, indent);
+    pretty
+        .write_all(json_str.as_bytes())
+        .expect("writing to a Vec cannot fail");
+    String::from_utf8(out).expect("re-indenting keeps UTF-8 intact")
+}
+
+/// Re-indents JSON as it is written, producing the same layout as
+/// `serde_json`'s pretty printer without parsing the whole document.
+/// Keys keep their order and values are copied through untouched.
+pub struct PrettyWriter<W> {
+    inner: W,
+    indent: Indent,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    just_opened: bool,
+    out: Vec<u8>,
+}
+
+impl<W: Write> PrettyWriter<W> {
+    pub fn new(inner: W, indent: Indent) -> Self {
+        Self {
+            inner,
+            indent,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            just_opened: false,
+            out: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn newline(&mut self) {
+        let unit: &[u8] = match self.indent {
+            Indent::Minified => return,
+            Indent::Spaces(width) => &b" ".repeat(width),
+            Indent::Tabs => b"\t",
+        };
+        self.out.push(b'\n');
+        for _ in 0..self.depth {
+            self.out.extend_from_slice(unit);
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.in_string {
+            self.out.push(byte);
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+            return;
+        }
+
+        match byte {
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+                if !self.just_opened {
+                    self.newline();
+                }
+                self.just_opened = false;
+                self.out.push(byte);
+            }
+            b',' => {
+                self.out.push(byte);
+                self.newline();
+            }
+            b':' => match self.indent {
+                Indent::Minified => self.out.push(byte),
+                _ => self.out.extend_from_slice(b": "),
+            },
+            _ => {
+                if self.just_opened {
+                    self.just_opened = false;
+                    self.newline();
+                }
+                self.out.push(byte);
+                match byte {
+                    b'{' | b'[' => {
+                        self.depth += 1;
+                        self.just_opened = true;
+                    }
+                    b'"' => self.in_string = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for PrettyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.push(byte);
+        }
+        self.inner.write_all(&self.out)?;
+        self.out.clear();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"b":[1,{"c":null}],"a":"x"}"#;
+
+    #[test]
+    fn indents_with_spaces_tabs_or_nothing() {
+        assert_eq!(
+            reindent(JSON, Indent::Spaces(2)),
+            "{\n  \"b\": [\n    1,\n    {\n      \"c\": null\n    }\n  ],\n  \"a\": \"x\"\n}"
+        );
+        assert_eq!(
+            reindent(r#"{"b":[1]}"#, Indent::Spaces(4)),
+            "{\n    \"b\": [\n        1\n    ]\n}"
+        );
+        assert_eq!(
+            reindent(JSON, Indent::Tabs),
+            "{\n\t\"b\": [\n\t\t1,\n\t\t{\n\t\t\t\"c\": null\n\t\t}\n\t],\n\t\"a\": \"x\"\n}"
+        );
+        assert_eq!(reindent("{ \"b\" : [ 1 ,\n\t2 ] }", Indent::Minified), r#"{"b":[1,2]}"#);
+    }
+
+    #[test]
+    fn reindent_matches_serde_json() {
+        for indent in [Indent::Spaces(2), Indent::Spaces(0), Indent::Tabs, Indent::Minified] {
+            assert_eq!(reindent(JSON, indent), format_json(JSON, indent, false));
+        }
+    }
+
+    #[test]
+    fn reindent_keeps_numbers_and_key_order() {
+        let json = r#"{"z":1.10,"y":1e2,"x":-0.0,"w":12345678901234567890}"#;
+        assert_eq!(reindent(json, Indent::Minified), json);
+        assert_eq!(
+            format_json(json, Indent::Minified, false),
+            r#"{"z":1.1,"y":100.0,"x":-0.0,"w":12345678901234567890}"#
+        );
+    }
+
+    #[test]
+    fn reindent_leaves_strings_alone() {
+        let json = r#"{"a":"{ [1, 2] }: ,","b":"say \"hi\", \\","c":"\u00e9\n"}"#;
+        assert_eq!(reindent(json, Indent::Minified), json);
+        assert_eq!(
+            reindent(json, Indent::Spaces(1)),
+            concat!(
+                "{\n \"a\": \"{ [1, 2] }: ,\",\n",
+                " \"b\": \"say \\\"hi\\\", \\\\\",\n",
+                " \"c\": \"\\u00e9\\n\"\n}"
+            )
+        );
+    }
+
+    #[test]
+    fn empty_containers_stay_on_one_line() {
+        assert_eq!(reindent("{ }", Indent::Spaces(2)), "{}");
+        assert_eq!(reindent("[\n]", Indent::Tabs), "[]");
+        assert_eq!(
+            reindent(r#"{"a":{},"b":[]}"#, Indent::Spaces(2)),
+            "{\n  \"a\": {},\n  \"b\": []\n}"
+        );
+    }
+
+    #[test]
+    fn canonical_sorts_keys_at_every_level() {
+        assert_eq!(
+            format_json(r#"{"b":{"d":1,"c":2},"a":[{"f":0,"e":0}]}"#, Indent::Minified, true),
+            r#"{"a":[{"e":0,"f":0}],"b":{"c":2,"d":1}}"#
+        );
+    }
+
+    #[test]
+    fn format_json_returns_invalid_text_unchanged() {
+        assert_eq!(format_json("{\"a\": ", Indent::Spaces(2), true), "{\"a\": ");
+    }
+
+    #[test]
+    fn pretty_writer_handles_split_writes() {
+        let mut out = Vec::new();
+        let mut pretty = PrettyWriter::new(&mut out, Indent::Spaces(2));
+        for chunk in [r#"{"a":"x\"#, r#"",y":[1"#, "2]}"] {
+            pretty.write_all(chunk.as_bytes()).unwrap();
+        }
+        let expected = "{\n  \"a\": \"x\\\",y\": [\n    12\n  ]\n}";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,288 @@
+//! Reading and writing WorldBox maps.
+//!
+//! A map is stored either as a zlib compressed `.wbox`/`.wbax` file or as
+//! plain JSON. [`read_map`] accepts both, and [`write_map`] writes either
+//! form depending on the output path or [`WriteOptions::encoding`]:
+//!
+//! ```no_run
+//! use pressor::{read_map, write_map, WriteOptions};
+//!
+//! let map = read_map("map.wbox")?;
+//! let saved = map.to_saved_map()?;
+//! println!("{:?}", saved.map_stats.and_then(|stats| stats.name));
+//! write_map(&map, "map.json", &WriteOptions::default())?;
+//! # Ok::<(), pressor::PressorError>(())
+//! ```
+
+pub mod atomic;
+pub mod backup;
+pub mod batch;
+pub mod deflate;
+pub mod diff;
+pub mod error;
+pub mod format;
+pub mod inflate;
+pub mod info;
+pub mod layout;
+pub mod map;
+pub mod merge;
+pub mod meta;
+pub mod patch;
+pub mod query;
+pub mod recover;
+pub mod render;
+pub mod roundtrip;
+pub mod slot;
+pub mod stream;
+pub mod validate;
+pub mod verify;
+
+pub use deflate::{compress, Level};
+pub use error::{PressorError, Result};
+pub use format::{detect_format, Format};
+pub use inflate::{decompress, DecompressError};
+pub use layout::Indent;
+pub use map::SavedMap;
+pub use validate::JsonError;
+
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Decompressed JSON of a map together with the format it was read from.
+#[derive(Debug, Clone)]
+pub struct Map {
+    json: String,
+    format: Format,
+}
+
+impl Map {
+    /// Reads a map from the contents of a compressed or plain JSON file.
+    /// Data of an unknown format is refused with the reason it cannot be
+    /// decompressed.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let format = detect_format(data);
+        let json = match format {
+            Format::ZlibJson | Format::GzipJson | Format::DeflateJson => decompress(data, format)?,
+            Format::PlainJson => text(data)?.to_string(),
+            Format::Unknown => {
+                let error = decompress(data, format).err().unwrap_or_else(|| {
+                    DecompressError::InvalidHeader("not a known compressed format".to_string())
+                });
+                return Err(error.into());
+            }
+        };
+        Ok(Self { json, format })
+    }
+
+    /// Reads `data` as JSON text, even if it does not start like JSON, so
+    /// that a broken map can be validated or compressed anyway.
+    pub fn from_text(data: &[u8]) -> Result<Self> {
+        Ok(Self::from_json(text(data)?))
+    }
+
+    pub fn from_json(json: impl Into<String>) -> Self {
+        Self {
+            json: json.into(),
+            format: Format::PlainJson,
+        }
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self> {
+        Ok(Self::from_json(serde_json::to_string(value)?))
+    }
+
+    pub fn from_saved_map(map: &SavedMap) -> Result<Self> {
+        Ok(Self::from_json(serde_json::to_string(map)?))
+    }
+
+    /// The JSON text exactly as it was read.
+    pub fn json(&self) -> &str {
+        &self.json
+    }
+
+    /// Format of the data the map was read from.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn to_value(&self) -> Result<Value> {
+        serde_json::from_str(&self.json).map_err(|e| self.json_error(&e))
+    }
+
+    pub fn to_saved_map(&self) -> Result<SavedMap> {
+        SavedMap::from_json(&self.json).map_err(|e| self.json_error(&e))
+    }
+
+    /// Encodes the map as it would be written by [`write_map`].
+    /// Returns the data and, for compressed output, the level used.
+    pub fn to_bytes(
+        &self,
+        encoding: Encoding,
+        options: &WriteOptions,
+    ) -> Result<(Vec<u8>, Option<u32>)> {
+        let (data, level, _) = self.encode(encoding, options)?;
+        Ok((data, level))
+    }
+
+    /// Like [`Map::to_bytes`], also returning the JSON text that was encoded.
+    fn encode(
+        &self,
+        encoding: Encoding,
+        options: &WriteOptions,
+    ) -> Result<(Vec<u8>, Option<u32>, Cow<'_, str>)> {
+        match encoding {
+            Encoding::Compressed => {
+                if options.validate {
+                    validate::validate_map(&self.json)?;
+                }
+                let text = if options.indent.is_some() || options.canonical {
+                    Cow::Owned(self.layout(options.indent.unwrap_or(Indent::Minified), options))
+                } else {
+                    Cow::Borrowed(self.json.as_str())
+                };
+                let (data, level) = compress(&text, options.level)
+                    .map_err(|e| PressorError::io("Compression error", e))?;
+                Ok((data, Some(level), text))
+            }
+            Encoding::Json => {
+                let text = self.layout(options.indent.unwrap_or_default(), options);
+                Ok((text.clone().into_bytes(), None, Cow::Owned(text)))
+            }
+        }
+    }
+
+    fn json_error(&self, error: &serde_json::Error) -> PressorError {
+        PressorError::InvalidJson(JsonError::from_serde(error, Some(&self.json)))
+    }
+
+    /// Only `canonical` parses the JSON; otherwise just the whitespace
+    /// changes, as when streaming.
+    fn layout(&self, indent: Indent, options: &WriteOptions) -> String {
+        if options.canonical {
+            layout::format_json(&self.json, indent, true)
+        } else {
+            layout::reindent(&self.json, indent)
+        }
+    }
+}
+
+/// How a map is stored on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// zlib compressed JSON, as the game saves it.
+    Compressed,
+    Json,
+}
+
+impl Encoding {
+    /// `.wbox` and `.wbax` files are compressed, anything else is JSON.
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase());
+        match ext.as_deref() {
+            Some("wbox") | Some("wbax") => Encoding::Compressed,
+            _ => Encoding::Json,
+        }
+    }
+}
+
+/// Settings for [`write_map`].
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// `None` picks the encoding from the output file extension.
+    pub encoding: Option<Encoding>,
+    pub level: Level,
+    /// Layout of the JSON. `None` writes pretty JSON with two spaces, and
+    /// keeps compressed JSON as it is.
+    pub indent: Option<Indent>,
+    /// Parse the JSON and sort object keys at every level. Otherwise only
+    /// whitespace changes, and numbers, strings and key order stay exactly
+    /// as read.
+    pub canonical: bool,
+    /// Check that the JSON is a valid map before compressing it.
+    pub validate: bool,
+    /// Read the file back before it replaces `path`, see
+    /// [`verify::verify_file`].
+    pub verify: bool,
+    /// Number of backups of a replaced `path` to keep, see [`backup`]. The
+    /// backup is only made once the new file is ready; 0 makes none.
+    pub backups: usize,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            encoding: None,
+            level: Level::default(),
+            indent: None,
+            canonical: false,
+            validate: true,
+            verify: true,
+            backups: 0,
+        }
+    }
+}
+
+/// What [`write_map`] wrote.
+#[derive(Clone, Debug)]
+pub struct Written {
+    pub encoding: Encoding,
+    pub size: u64,
+    /// Compression level used for compressed output.
+    pub level: Option<u32>,
+    /// Backup of the map that was replaced, if one was made.
+    pub backup: Option<PathBuf>,
+}
+
+/// Reads a compressed or plain JSON map file.
+pub fn read_map(path: impl AsRef<Path>) -> Result<Map> {
+    let path = path.as_ref();
+    let data = fs::read(path)
+        .map_err(|e| PressorError::io(format!("File reading error {}", path.display()), e))?;
+    Map::from_bytes(&data)
+}
+
+/// Writes `map` to `path`, compressed or as JSON. The file is replaced
+/// atomically, see [`atomic`], and only after it was verified if
+/// `options.verify` is set and backed up if `options.backups` is not 0.
+pub fn write_map(map: &Map, path: impl AsRef<Path>, options: &WriteOptions) -> Result<Written> {
+    let path = path.as_ref();
+    let encoding = options.encoding.unwrap_or_else(|| Encoding::from_path(path));
+    let (data, level, text) = map.encode(encoding, options)?;
+    let error = |e| PressorError::io(format!("File writing error in {}", path.display()), e);
+    let mut file = atomic::AtomicFile::create(path).map_err(error)?;
+    file.write_all(&data).map_err(error)?;
+    if options.verify {
+        verify::verify_file(file.temp_path(), path, &text, map.json())?;
+    }
+    let backup = backup::create(path, options.backups)?;
+    file.commit().map_err(error)?;
+    Ok(Written {
+        encoding,
+        size: data.len() as u64,
+        level,
+        backup,
+    })
+}
+
+/// Whether `a` and `b` are the same existing file, under any name.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Map text without a byte order mark, checked to be UTF-8.
+pub(crate) fn text(data: &[u8]) -> Result<&str> {
+    std::str::from_utf8(format::strip_bom(data)).map_err(|e| PressorError::InvalidUtf8 {
+        offset: e.valid_up_to() as u64,
+    })
+}
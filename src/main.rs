@@ -1,23 +1,82 @@
+mod cli;
+mod convert;
+
 use anyhow::{Context, Result};
-use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use cli::{
+    Command, Direction, Invocation, LawsAction, MetaAction, Options, RestoreAction, Writing,
+};
+use pressor::{
+    atomic, backup,
+    diff::{self, EntityChanges, TileChanges},
+    format,
+    info::MapInfo,
+    layout,
+    map::{self, WORLD_LAWS},
+    merge,
+    meta::MapMeta,
+    patch::{self, PatchKind},
+    query::Query,
+    recover,
+    render::{self, Palette, RenderOptions},
+    roundtrip, same_file, slot, validate, write_map, Indent, Map, PressorError, WriteOptions,
+    Written,
+};
+#[cfg(feature = "dialogs")]
 use rfd::FileDialog;
-use serde_json::{from_str, to_string_pretty, Value};
 use std::{
     env,
     fs,
-    io::{self, Read, Write},
-    path::PathBuf,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::SystemTime,
 };
 
-fn main() -> Result<()> {
-    let result = run();
-    
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
+fn main() -> ExitCode {
+    let invocation = match cli::parse(env::args().skip(1).collect()) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("Error: {}\nRun `pressor --help` for usage.", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    match invocation {
+        Invocation::Help => {
+            println!("{}", cli::USAGE);
+            ExitCode::SUCCESS
+        }
+        Invocation::Command(command) => match run_command(command) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                exit_code(&e)
+            }
+        },
+        Invocation::Interactive(input) => {
+            let result = run(input);
+
+            if let Err(e) = &result {
+                eprintln!("Error: {}", e);
+            }
+
+            wait_for_enter();
+            match &result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => exit_code(e),
+            }
+        }
     }
-    
-    wait_for_enter();
-    Ok(())
+}
+
+/// Exit code of the first [`PressorError`] behind `error`, or 1 for
+/// failures that have no code of their own.
+fn exit_code(error: &anyhow::Error) -> ExitCode {
+    let code = error
+        .chain()
+        .find_map(|e| e.downcast_ref::<PressorError>())
+        .map_or(1, PressorError::exit_code);
+    ExitCode::from(code)
 }
 
 fn wait_for_enter() {
@@ -26,145 +85,769 @@ fn wait_for_enter() {
     let _ = io::stdin().read_line(&mut input);
 }
 
-fn run() -> Result<()> {
-    let input_path: PathBuf = {
-        let mut args = env::args().skip(1);
-        if let Some(arg) = args.next() {
-            let p = PathBuf::from(arg);
-            if !p.exists() {
-                return Err(anyhow::anyhow!(
-                    "Input file does not exist: {}",
-                    p.display()
-                ));
+fn run_command(command: Command) -> Result<()> {
+    // Save slot folders stand for the map file inside them.
+    let input = |path: &Path| slot::resolve_input(path);
+    let output = |path: &Path| slot::resolve_output(path);
+    match command {
+        Command::Convert(convert) => convert::run(convert),
+        Command::Recover {
+            input: from,
+            output: to,
+            writing,
+        } => run_recover(&input(&from), &output(&to), writing),
+        Command::VerifyRoundTrip { input: map } => run_verify_roundtrip(&input(&map)),
+        Command::Info { input, json } => run_info(&input, json),
+        Command::Diff { old, new, as_patch } => run_diff(&input(&old), &input(&new), as_patch),
+        Command::Merge {
+            base,
+            ours,
+            theirs,
+            output: to,
+            writing,
+        } => run_merge(&input(&base), &input(&ours), &input(&theirs), &output(&to), writing),
+        Command::Render {
+            input: map,
+            output,
+            palette,
+            options,
+            writing,
+        } => run_render(&input(&map), &output, palette.as_deref(), options, writing),
+        Command::Laws { input: map, action } => {
+            let action = match action {
+                LawsAction::Set {
+                    changes,
+                    output: to,
+                    writing,
+                } => LawsAction::Set {
+                    changes,
+                    output: output(&to),
+                    writing,
+                },
+                list => list,
+            };
+            run_laws(&input(&map), action)
+        }
+        Command::Query {
+            input: map,
+            query,
+            indent,
+        } => run_query(&input(&map), &query, indent.unwrap_or_default()),
+        Command::Patch {
+            input: map,
+            patch,
+            output: to,
+            writing,
+        } => run_patch(&input(&map), &patch, &output(&to), writing),
+        Command::Restore { input: map, action } => run_restore(&input(&map), action),
+        Command::ExportSlot {
+            slot,
+            output,
+            writing,
+        } => run_export_slot(&slot, &output, writing),
+        Command::ImportSlot {
+            input,
+            slot,
+            writing,
+        } => run_import_slot(&input, &slot, writing),
+        Command::Meta { slot, action } => run_meta(&slot, action),
+    }
+}
+
+fn run_export_slot(slot_dir: &Path, out_dir: &Path, writing: Writing) -> Result<()> {
+    if !slot_dir.is_dir() {
+        return Err(PressorError::NotFound(slot_dir.to_path_buf()).into());
+    }
+    transfer_slot(slot_dir, out_dir, slot::JSON_FILE, writing)
+}
+
+fn run_import_slot(input_dir: &Path, slot_dir: &Path, writing: Writing) -> Result<()> {
+    if !input_dir.is_dir() {
+        return Err(PressorError::NotFound(input_dir.to_path_buf()).into());
+    }
+    transfer_slot(input_dir, slot_dir, slot::MAP_FILE, writing)
+}
+
+/// Checks that the files [`slot::transfer`] writes may be replaced, then
+/// runs it.
+fn transfer_slot(from: &Path, to: &Path, map_name: &str, writing: Writing) -> Result<()> {
+    prepare_output(&[&slot::find_map(from)?], &to.join(map_name), writing)?;
+    for name in slot::SLOT_FILES {
+        let source = from.join(name);
+        if source.is_file() {
+            prepare_output(&[&source], &to.join(name), writing)?;
+        }
+    }
+
+    let transfer = slot::transfer(from, to, map_name, &map_options(writing))?;
+    if let Some(backup) = &transfer.written.backup {
+        println!("▌ Backup saved in: {}", backup.display());
+    }
+    println!("▌ Map saved in: {} ({} bytes)", transfer.map.display(), transfer.written.size);
+    for name in &transfer.missing {
+        println!("▌ No {} in {}", name, from.display());
+    }
+    for path in &transfer.copied {
+        println!("▌ Copied: {}", path.display());
+    }
+    if let Some(meta) = &transfer.meta {
+        println!("▌ Updated: {}", meta.display());
+    }
+    Ok(())
+}
+
+fn run_restore(map_path: &Path, action: RestoreAction) -> Result<()> {
+    let (selected, keep, verify) = match action {
+        RestoreAction::List => {
+            let backups = backup::list(map_path)?;
+            if backups.is_empty() {
+                println!("▌ No backups of {}", map_path.display());
             }
-            p
-        } else {
-            println!("Select the file to be processed...");
-            let picked = open_file_dialog().context("Failed to select file")?;
-            if picked.to_str().is_none() {
-                println!("File is not selected");
-                return Ok(());
+            for (number, backup) in backups.iter().rev().enumerate() {
+                println!("▌ {}. {} ({} bytes)", number + 1, backup.timestamp, backup.size);
             }
-            picked
+            return Ok(());
         }
+        RestoreAction::Restore {
+            backup,
+            keep,
+            verify,
+        } => (backup, keep, verify),
     };
 
-    let ext = input_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase());
-    match ext.as_deref() {
-        Some("wbox") | Some("wbax") | Some("json") => {
+    let Some(backup) = backup::find(map_path, selected.as_deref())? else {
+        return Err(anyhow::anyhow!(
+            "No backup {}of {}, run `pressor restore {} --list` for its backups",
+            selected.map(|name| format!("{} ", name)).unwrap_or_default(),
+            map_path.display(),
+            map_path.display()
+        ));
+    };
+    let replaced = backup::restore(map_path, &backup, keep, verify)
+        .with_context(|| format!("Cannot restore the backup {}", backup.path.display()))?;
+    if let Some(replaced) = replaced {
+        println!("▌ Backup saved in: {}", replaced.display());
+    }
+    println!("▌ Restored {} from the backup of {}", map_path.display(), backup.timestamp);
+    Ok(())
+}
+
+fn run_patch(
+    input_path: &Path,
+    patch_path: &Path,
+    output_path: &Path,
+    writing: Writing,
+) -> Result<()> {
+    check_input(input_path)?;
+    prepare_output(&[input_path, patch_path], output_path, writing)?;
+    let mut value = Map::from_bytes(&read_input(input_path)?)?.to_value()?;
+    let patch = Map::from_bytes(&read_input(patch_path)?)?
+        .to_value()
+        .with_context(|| format!("Invalid patch {}", patch_path.display()))?;
+
+    match patch::apply(&mut value, &patch)? {
+        PatchKind::JsonPatch => println!(
+            "▌ Applied {} operations",
+            patch.as_array().map_or(0, Vec::len)
+        ),
+        PatchKind::MergePatch => println!("▌ Applied merge patch"),
+    }
+    let written = save_map(&Map::from_value(&value)?, output_path, &map_options(writing))?;
+    println!("▌ The result is saved in: {} ({} bytes)", output_path.display(), written.size);
+    Ok(())
+}
+
+fn run_query(input_path: &Path, query: &Query, indent: Indent) -> Result<()> {
+    check_input(input_path)?;
+    let value = Map::from_bytes(&read_input(input_path)?)?.to_value()?;
+    let matches = query.select(&value);
+    for value in &matches {
+        println!("{}", layout::to_string_with_indent(value, indent)?);
+    }
+    eprintln!("▌ Matches: {}", matches.len());
+    Ok(())
+}
+
+fn run_laws(input_path: &Path, action: LawsAction) -> Result<()> {
+    check_input(input_path)?;
+    let map = Map::from_bytes(&read_input(input_path)?)?;
+    let laws = map.to_saved_map()?.world_laws.unwrap_or_default();
+
+    let (changes, output_path, writing) = match action {
+        LawsAction::List => {
+            if laws.list.is_empty() {
+                println!("▌ The map has no world laws");
+            }
+            for law in &laws.list {
+                let value = law.bool_val.map_or("unset".to_string(), |v| v.to_string());
+                println!("▌ {}: {}", law.name, value);
+            }
+            return Ok(());
         }
-        _ => {
+        LawsAction::Set {
+            changes,
+            output,
+            writing,
+        } => (changes, output, writing),
+    };
+    prepare_output(&[input_path], &output_path, writing)?;
+
+    // Only worldLaws is edited, the rest of the JSON is written back as read.
+    let mut json = map.to_value()?;
+    for (name, value) in changes {
+        let known = |name: &str| {
+            WORLD_LAWS.contains(&name) || laws.list.iter().any(|law| law.name == name)
+        };
+        let prefixed = format!("world_law_{}", name);
+        let name = if known(&name) {
+            name
+        } else if known(&prefixed) {
+            prefixed
+        } else {
             return Err(anyhow::anyhow!(
-                "Unsupported file extension: {:?}. Allowed extensions are .wbox, .wbax, .json",
-                input_path.extension().and_then(|e| e.to_str())
+                "Unknown law {}, run `pressor laws {} --list` for the laws of the map",
+                name,
+                input_path.display()
             ));
+        };
+        let old = map::set_law(&mut json, &name, value)?;
+        let old = old.map_or("unset".to_string(), |v| v.to_string());
+        println!("▌ {}: {} -> {}", name, old, value);
+    }
+
+    let written = save_map(&Map::from_value(&json)?, &output_path, &map_options(writing))?;
+    println!("▌ The result is saved in: {} ({} bytes)", output_path.display(), written.size);
+    Ok(())
+}
+
+fn run_render(
+    input_path: &Path,
+    output_path: &Path,
+    palette_path: Option<&Path>,
+    options: RenderOptions,
+    writing: Writing,
+) -> Result<()> {
+    let mut palette = Palette::default();
+    if let Some(path) = palette_path {
+        let text = String::from_utf8(read_input(path)?)
+            .with_context(|| format!("Palette {} is not UTF-8 text", path.display()))?;
+        let colors: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&text)
+            .map_err(|e| PressorError::from(validate::JsonError::from_serde(&e, Some(&text))))
+            .with_context(|| format!("Invalid palette {}", path.display()))?;
+        for (tile, color) in colors {
+            let rgb = color.as_str().and_then(render::parse_color).with_context(|| {
+                format!("Invalid colour for {} in the palette, expected \"#rrggbb\"", tile)
+            })?;
+            palette.set(tile, rgb);
+        }
+    }
+
+    check_input(input_path)?;
+    prepare_output(&[input_path], output_path, writing)?;
+    let map = Map::from_bytes(&read_input(input_path)?)?.to_saved_map()?;
+    let image = render::render(&map, &palette, options)
+        .ok_or_else(|| anyhow::anyhow!("Map has no tile data to render"))?;
+    let mut png = Vec::new();
+    image.write_png(&mut png).context("PNG encoding error")?;
+    atomic::write(output_path, &png).map_err(|e| {
+        PressorError::io(format!("File writing error in {}", output_path.display()), e)
+    })?;
+
+    println!("▌ Picture of {} x {} tiles", image.width, image.height);
+    println!("▌ The result is saved in: {} ({} bytes)", output_path.display(), png.len());
+    Ok(())
+}
+
+fn run_merge(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    output_path: &Path,
+    writing: Writing,
+) -> Result<()> {
+    for path in [base, ours, theirs] {
+        check_input(path)?;
+    }
+    prepare_output(&[base, ours, theirs], output_path, writing)?;
+    let mut values = Vec::new();
+    for path in [base, ours, theirs] {
+        values.push(Map::from_bytes(&read_input(path)?)?.to_value()?);
+    }
+    let merged = merge::merge(&values[0], &values[1], &values[2]);
+    let written = save_map(
+        &Map::from_value(&merged.value)?,
+        output_path,
+        &map_options(writing),
+    )?;
+
+    println!("▌ The result is saved in: {} ({} bytes)", output_path.display(), written.size);
+    if merged.conflicts.is_empty() {
+        println!("▌ Merged without conflicts");
+        return Ok(());
+    }
+    println!("▌ Conflicts, resolved with OURS:");
+    for conflict in &merged.conflicts {
+        println!("▌     {}", conflict);
+    }
+    Err(anyhow::anyhow!("{} conflicts while merging", merged.conflicts.len()))
+}
+
+fn run_diff(old_path: &Path, new_path: &Path, as_patch: bool) -> Result<()> {
+    let mut maps = Vec::new();
+    for path in [old_path, new_path] {
+        check_input(path)?;
+        maps.push(Map::from_bytes(&read_input(path)?)?);
+    }
+    if as_patch {
+        let operations = patch::diff(&maps[0].to_value()?, &maps[1].to_value()?);
+        println!("{}", serde_json::to_string_pretty(&operations)?);
+        return Ok(());
+    }
+
+    let maps = [maps[0].to_saved_map()?, maps[1].to_saved_map()?];
+    let diff = diff::diff(&maps[0], &maps[1]);
+    if diff.is_empty() {
+        println!("▌ No differences");
+        return Ok(());
+    }
+
+    if !diff.laws.is_empty() {
+        println!("▌ World laws:");
+        for law in &diff.laws {
+            let state = |value: Option<bool>| value.map_or("absent".to_string(), |v| v.to_string());
+            println!("▌     {}: {} -> {}", law.name, state(law.before), state(law.after));
+        }
+    }
+    print_entity_changes("Kingdoms", &diff.kingdoms);
+    print_entity_changes("Cities", &diff.cities);
+    if diff.actors_before != diff.actors_after || !diff.species.is_empty() {
+        println!(
+            "▌ Actors: {} -> {} ({:+})",
+            diff.actors_before,
+            diff.actors_after,
+            diff.actors_after as i64 - diff.actors_before as i64
+        );
+        for species in &diff.species {
+            println!(
+                "▌     {}: {} -> {} ({:+})",
+                species.name,
+                species.before,
+                species.after,
+                species.after as i64 - species.before as i64
+            );
+        }
+    }
+    match &diff.tiles {
+        TileChanges::None => {}
+        TileChanges::Resized { before, after } => println!(
+            "▌ World resized from {} x {} to {} x {} tiles, tiles not compared",
+            before.0, before.1, after.0, after.1
+        ),
+        TileChanges::Changed(regions) => {
+            let total: usize = regions.iter().map(|r| r.changed).sum();
+            println!("▌ Tiles changed: {} in {} regions", total, regions.len());
+            for region in regions {
+                println!(
+                    "▌     x {}-{}, y {}-{}: {} tiles",
+                    region.x,
+                    region.x + diff::REGION_SIZE - 1,
+                    region.y,
+                    region.y + diff::REGION_SIZE - 1,
+                    region.changed
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_entity_changes(label: &str, changes: &EntityChanges) {
+    for (what, entities) in [("added", &changes.added), ("removed", &changes.removed)] {
+        if entities.is_empty() {
+            continue;
+        }
+        println!("▌ {} {}:", label, what);
+        for entity in entities {
+            let name = entity.name.as_deref().unwrap_or("unnamed");
+            match &entity.id {
+                Some(id) => println!("▌     {} (id {})", name, id),
+                None => println!("▌     {}", name),
+            }
         }
     }
+}
+
+fn run_info(path: &Path, json: bool) -> Result<()> {
+    let input_path = &slot::resolve_input(path);
+    check_input(input_path)?;
+    let data = read_input(input_path)?;
+    let map = Map::from_bytes(&data)?;
+    let mut info = MapInfo::new(&map, data.len() as u64)?;
+    if path.is_dir() {
+        info.meta = slot::read_meta(path)?;
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let unknown = || "unknown".to_string();
+    println!("▌ Name: {}", info.name.clone().unwrap_or_else(unknown));
+    println!("▌ Save version: {}", info.save_version.map_or_else(unknown, |v| v.to_string()));
+    let size = match (info.width, info.height) {
+        (Some(width), Some(height)) => format!("{} x {}", width, height),
+        _ => unknown(),
+    };
+    match info.tiles {
+        Some(tiles) => {
+            println!("▌ World size: {} ({} x {} tiles)", size, tiles.width, tiles.height)
+        }
+        None => println!("▌ World size: {}", size),
+    }
+    match (info.world_age, info.world_time) {
+        (Some(age), Some(time)) => println!("▌ World age: {} years ({:.0} s)", age, time),
+        _ => println!("▌ World age: unknown"),
+    }
+
+    println!("▌ Actors: {}", info.actors);
+    let mut species: Vec<_> = info.species.iter().collect();
+    species.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (asset, count) in species {
+        println!("▌     {}: {}", asset, count);
+    }
+    println!("▌ Buildings: {}", info.buildings);
+    println!("▌ Cities: {}", info.cities);
+    println!("▌ Kingdoms: {}", info.kingdoms);
+    println!("▌ Cultures: {}", info.cultures);
+    println!("▌ Clans: {}", info.clans);
+    println!("▌ Wars: {}", info.wars);
+    println!("▌ Alliances: {}", info.alliances);
+
+    println!(
+        "▌ Compressed size: {} bytes{}",
+        info.compressed_size,
+        if info.compressed_estimated { " (at the default level)" } else { "" }
+    );
+    println!("▌ Decompressed size: {} bytes", info.decompressed_size);
+    println!("▌ Compression ratio: {:.2}:1", info.ratio);
+
+    if let Some(meta) = &info.meta {
+        println!("▌ Slot metadata ({}):", slot::META_FILE);
+        if print_meta(meta, &MapMeta::from_saved_map(&map.to_saved_map()?)) {
+            println!("▌ Run `pressor meta {} --update` to refresh it", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn run_meta(path: &Path, action: MetaAction) -> Result<()> {
+    // A map file stands for the slot folder it is in.
+    let (dir, map_path) = if path.is_file() {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        (dir, path.to_path_buf())
+    } else {
+        (path, slot::find_map(path)?)
+    };
+    let map = Map::from_bytes(&read_input(&map_path)?)?.to_saved_map()?;
+    let meta_path = dir.join(slot::META_FILE);
+    let meta = slot::read_meta(dir)?;
+
+    match action {
+        MetaAction::Show { json } => {
+            let Some(meta) = meta else {
+                return Err(PressorError::NotFound(meta_path).into());
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&meta)?);
+            } else if print_meta(&meta, &MapMeta::from_saved_map(&map)) {
+                println!("▌ Run `pressor meta {} --update` to refresh it", path.display());
+            }
+        }
+        MetaAction::Update { game_version } => {
+            let mut meta = meta.unwrap_or_default();
+            meta.update(&map, SystemTime::now());
+            if game_version.is_some() {
+                meta.game_version = game_version;
+            }
+            meta.write(&meta_path)?;
+            println!("▌ Updated: {}", meta_path.display());
+            print_meta(&meta, &meta);
+        }
+    }
+    Ok(())
+}
+
+/// Prints the fields of `meta`, each followed by the value in `from_map`
+/// where they differ. Returns whether any did.
+fn print_meta(meta: &MapMeta, from_map: &MapMeta) -> bool {
+    let size = |m: &MapMeta| m.width.zip(m.height).map(|(w, h)| format!("{} x {}", w, h));
+    let number = |n: Option<u64>| n.map(|n| n.to_string());
+    let rows = [
+        ("Name", meta.name.clone(), from_map.name.clone()),
+        ("Description", meta.description.clone(), from_map.description.clone()),
+        ("World size", size(meta), size(from_map)),
+        ("Population", number(meta.population), number(from_map.population)),
+        ("Cities", number(meta.cities), number(from_map.cities)),
+        ("Kingdoms", number(meta.kingdoms), number(from_map.kingdoms)),
+        ("Save time", meta.save_time.as_ref().map(ToString::to_string), None),
+        ("Game version", meta.game_version.clone(), None),
+    ];
+    let mut stale = false;
+    for (label, value, expected) in rows {
+        let text = value.clone().unwrap_or_else(|| "unknown".to_string());
+        match expected {
+            Some(expected) if value.as_ref() != Some(&expected) => {
+                stale = true;
+                println!("▌     {}: {} (map: {})", label, text, expected);
+            }
+            _ => println!("▌     {}: {}", label, text),
+        }
+    }
+    stale
+}
+
+fn run_verify_roundtrip(input_path: &Path) -> Result<()> {
+    check_input(input_path)?;
+    let result = roundtrip::verify(&read_input(input_path)?)?;
+
+    println!("▌ Original size: {} bytes", result.original_len);
+    println!("▌ Exported JSON size: {} bytes", result.exported_len);
+    println!("▌ Recompressed size: {} bytes", result.recompressed_len);
+    match &result.parsed_mismatch {
+        None => println!("▌ Parsing the JSON, as --canonical does, keeps its values unchanged"),
+        Some(mismatch) => {
+            println!(
+                "▌ Parsing the JSON, as --canonical does, changes it at token offset {}:",
+                mismatch.offset
+            );
+            println!("    original: {}", mismatch.expected);
+            println!("    parsed:   {}", mismatch.actual);
+        }
+    }
+
+    if let Some(mismatch) = &result.exact_mismatch {
+        return Err(anyhow::anyhow!(
+            "Round trip changed the JSON at token offset {}\n    original: {}\n    restored: {}",
+            mismatch.offset,
+            mismatch.expected,
+            mismatch.actual
+        ));
+    }
+    println!("\n▌ Round trip is exact: decompress and compress give back the same JSON");
+    Ok(())
+}
+
+fn run_recover(input_path: &Path, output_path: &Path, writing: Writing) -> Result<()> {
+    check_input(input_path)?;
+    prepare_output(&[input_path], output_path, writing)?;
+    let data = read_input(input_path)?;
+    let recovery = recover::recover(&data);
+
+    let options = WriteOptions {
+        validate: false,
+        ..map_options(writing)
+    };
+    let written = save_map(&Map::from_json(recovery.json.as_str()), output_path, &options)?;
+
+    match &recovery.error {
+        Some(e) => println!("▌ Decompression stopped: {}", e),
+        None => println!("▌ Decompression finished without errors"),
+    }
+    println!("▌ Decompressed: {} bytes", recovery.decompressed_len);
+    println!("▌ Discarded from the end: {} bytes", recovery.discarded);
+    if let Some(section) = &recovery.incomplete_section {
+        println!("▌ Incomplete section: {}", section);
+    }
+    println!("▌ Recovered sections: {}", list_or_none(&recovery.sections));
+    println!("▌ Missing sections: {}", list_or_none(&recovery.missing_sections()));
+    println!("▌ The result is saved in: {} ({} bytes)", output_path.display(), written.size);
+    Ok(())
+}
 
-    let input_size = fs::metadata(&input_path)
-        .with_context(|| format!("Failed to get the file size {}", input_path.display()))?
-        .len();
+fn list_or_none<S: AsRef<str>>(items: &[S]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn run(input: Option<PathBuf>) -> Result<()> {
+    let input_path: PathBuf = {
+        if let Some(p) = input {
+            if !p.exists() {
+                return Err(PressorError::NotFound(p).into());
+            }
+            slot::resolve_input(&p)
+        } else {
+            println!("Select the file to be processed...");
+            open_file_dialog()
+                .ok_or(PressorError::Cancelled)
+                .context("File is not selected")?
+        }
+    };
+
+    check_extension(&input_path)?;
+
+    let data = read_input(&input_path)?;
     println!("\n▌ File selected: {}", input_path.display());
-    println!("▌ File size: {} bytes", input_size);
+    println!("▌ File size: {} bytes", data.len());
 
-    let is_compressed = is_file_compressed(&input_path)?;
+    let format = format::detect_format(&data);
+    let is_compressed = format.is_compressed();
     println!(
-        "▌ File {} compressed",
-        if is_compressed { "is" } else { "is not" }
+        "▌ File {} compressed ({})",
+        if is_compressed { "is" } else { "is not" },
+        format
     );
 
     let default_extension = if is_compressed { "json" } else { "wbox" };
     let suggested_name = format!(
         "{}.{}",
-        input_path.file_stem().unwrap().to_str().unwrap(),
+        input_path.file_stem().unwrap_or_default().to_string_lossy(),
         default_extension
     );
 
     println!("\nSpecify the path to save the file...");
-    let output_path = save_file_dialog(&suggested_name).context("Failed to save file")?;
-    if output_path.to_str().is_none() {
-        println!("▌ File is not saved");
-        return Ok(());
+    let output_path = save_file_dialog(&suggested_name)
+        .ok_or(PressorError::Cancelled)
+        .context("File is not saved")?;
+    if !confirm_output(&input_path, &output_path) {
+        return Err(PressorError::Cancelled).context("File is not saved");
     }
-
-    if is_compressed {
-        let compressed_data = fs::read(&input_path)?;
-        let decompressed_data = decompress(&compressed_data)?;
-        let formatted_json = format_json(&decompressed_data);
-
-        fs::write(&output_path, formatted_json)
-            .with_context(|| format!("File writing error in {}", output_path.display()))?;
-        
-        let output_size = fs::metadata(&output_path)
-            .with_context(|| format!("Failed to verify file size {}", output_path.display()))?
-            .len();
-        
-        println!("\n▌ File has been successfully decompressed!");
-        println!("▌ Original size: {} bytes", input_size);
-        println!("▌ Size after decompressing: {} bytes", output_size);
-        println!("▌ The result is saved in: {}", output_path.display());
+    let direction = if is_compressed {
+        Direction::Decompress
     } else {
-        let text = fs::read_to_string(&input_path)?;
-        let compressed_data = compress(&text)?;
-
-        fs::write(&output_path, compressed_data)
-            .with_context(|| format!("File writing error in {}", output_path.display()))?;
+        Direction::Compress
+    };
+    convert::convert_data(direction, &data, &output_path, &Options::default())
+}
 
-        let output_size = fs::metadata(&output_path)
-            .with_context(|| format!("Failed to verify file size {}", output_path.display()))?
-            .len();
-        
-        println!("\n▌ File has been successfully compressed!");
-        println!("▌ Original size: {} bytes", input_size);
-        println!("▌ Size after compressing: {} bytes", output_size);
-        println!("▌ The result is saved in: {}", output_path.display());
+fn check_input(path: &Path) -> pressor::Result<()> {
+    if !path.exists() {
+        return Err(PressorError::NotFound(path.to_path_buf()));
+    }
+    if path.is_dir() {
+        // A folder that is not a save slot.
+        return slot::find_map(path).map(drop);
     }
+    check_extension(path)
+}
 
+/// Refuses to replace an existing `output`, or one of `inputs`, unless
+/// `writing` allows it. Backups are made once the new output is ready.
+fn prepare_output(inputs: &[&Path], output: &Path, writing: Writing) -> Result<()> {
+    if let Some(input) = inputs.iter().find(|input| same_file(input, output)) {
+        if !writing.in_place {
+            return Err(anyhow::anyhow!(
+                "Output {} is the input file {}, use --in-place to replace it",
+                output.display(),
+                input.display()
+            ));
+        }
+    } else if output.exists() && !writing.force {
+        return Err(anyhow::anyhow!(
+            "Output file {} already exists, use --force to replace it",
+            output.display()
+        ));
+    }
     Ok(())
 }
 
+/// Like [`write_map`], but prints where the replaced map was backed up.
+fn save_map(map: &Map, path: &Path, options: &WriteOptions) -> Result<Written> {
+    let written = write_map(map, path, options)?;
+    if let Some(backup) = &written.backup {
+        println!("▌ Backup saved in: {}", backup.display());
+    }
+    Ok(written)
+}
+
+/// Asks before the interactive mode replaces the input file, or any other
+/// file when the save dialog did not ask already.
+fn confirm_output(input: &Path, output: &Path) -> bool {
+    if same_file(input, output) {
+        return confirm("▌ This is the input file. Replace it? [y/N] ");
+    }
+    if cfg!(not(feature = "dialogs")) && output.exists() {
+        return confirm(&format!("▌ {} already exists. Replace it? [y/N] ", output.display()));
+    }
+    true
+}
 
+fn confirm(prompt: &str) -> bool {
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line).is_err() {
+        return false;
+    }
+    matches!(line.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn check_extension(path: &Path) -> pressor::Result<()> {
+    if slot::is_map_file(path) {
+        Ok(())
+    } else {
+        Err(PressorError::UnsupportedExtension(path.to_path_buf()))
+    }
+}
+
+fn read_input(path: &Path) -> pressor::Result<Vec<u8>> {
+    fs::read(path)
+        .map_err(|e| PressorError::io(format!("File reading error {}", path.display()), e))
+}
+
+/// Write options of commands that write a map with the default layout.
+fn map_options(writing: Writing) -> WriteOptions {
+    WriteOptions {
+        verify: writing.verify,
+        backups: writing.backups,
+        ..WriteOptions::default()
+    }
+}
+
+#[cfg(feature = "dialogs")]
 fn open_file_dialog() -> Option<PathBuf> {
     FileDialog::new()
         .add_filter("Files", &["wbox", "wbax", "json"])
         .pick_file()
 }
 
+#[cfg(feature = "dialogs")]
 fn save_file_dialog(suggested_name: &str) -> Option<PathBuf> {
     FileDialog::new()
         .set_file_name(suggested_name)
         .save_file()
 }
 
-fn is_file_compressed(path: &PathBuf) -> Result<bool> {
-    let data = fs::read(path)
-        .with_context(|| format!("File reading error {}", path.display()))?;
-    
-    Ok(decompress(&data).is_ok())
+#[cfg(not(feature = "dialogs"))]
+fn open_file_dialog() -> Option<PathBuf> {
+    prompt_path("Path of the file: ")
 }
 
-fn decompress(data: &[u8]) -> Result<String> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut result = String::new();
-    decoder.read_to_string(&mut result)?;
-    Ok(result)
+#[cfg(not(feature = "dialogs"))]
+fn save_file_dialog(suggested_name: &str) -> Option<PathBuf> {
+    prompt_path(&format!("Path to save the file [{}]: ", suggested_name))
+        .or_else(|| Some(PathBuf::from(suggested_name)))
 }
 
-fn compress(text: &str) -> Result<Vec<u8>> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(text.as_bytes())?;
-    Ok(encoder.finish()?)
-}
+/// Asks for a path on the console when file dialogs are not compiled in.
+#[cfg(not(feature = "dialogs"))]
+fn prompt_path(prompt: &str) -> Option<PathBuf> {
+    use std::io::Write;
 
-fn format_json(json_str: &str) -> String {
-    match from_str::<Value>(json_str) {
-        Ok(parsed) => match to_string_pretty(&parsed) {
-            Ok(pretty) => pretty,
-            Err(_) => json_str.to_string(),
-        },
-        Err(_) => json_str.to_string(),
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    let line = line.trim();
+    if line.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(line))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,339 @@
+//! Typed model of the WorldBox `SavedMap` JSON.
+//!
+//! Only the fields the tools work with are typed. Everything else is kept
+//! in the `extra` map of the enclosing struct, so a map that is read and
+//! written back keeps all of its values, with two exceptions: typed fields
+//! that are `null` are dropped, and typed fields are written before the
+//! others, which can change the key order. Edits that must leave the rest
+//! of a map exactly as it was are made on its [`Value`] instead.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// Top-level sections of a save, in the order the game writes them.
+pub const SECTIONS: &[&str] = &[
+    "saveVersion",
+    "width",
+    "height",
+    "mapStats",
+    "worldLaws",
+    "tileMap",
+    "tileArray",
+    "tileAmounts",
+    "fire",
+    "conwayEater",
+    "conwayCreator",
+    "frozen_tiles",
+    "actors_data",
+    "buildings",
+    "cities",
+    "kingdoms",
+    "cultures",
+    "clans",
+    "alliances",
+    "wars",
+    "plots",
+];
+
+/// World laws the game knows, as named in `worldLaws`.
+pub const WORLD_LAWS: &[&str] = &[
+    "world_law_diplomacy",
+    "world_law_peaceful_monsters",
+    "world_law_hunger",
+    "world_law_old_age",
+    "world_law_civ_babies",
+    "world_law_animals_babies",
+    "world_law_animals_spawn",
+    "world_law_angry_civilians",
+    "world_law_rebellions",
+    "world_law_border_stealing",
+    "world_law_kingdom_expansion",
+    "world_law_civ_army",
+    "world_law_civ_migrants",
+    "world_law_vegetation_random_seeds",
+    "world_law_grow_trees",
+    "world_law_grow_grass",
+    "world_law_erosion",
+    "world_law_spread_fire",
+    "world_law_spread_fungi",
+    "world_law_forever_lava",
+    "world_law_forever_cold",
+    "world_law_cursed_world",
+    "world_law_gaias_covenant",
+    "world_law_disasters_nature",
+    "world_law_disasters_other",
+];
+
+/// A whole save. Sections missing from the file stay `None` and are not
+/// written back.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedMap {
+    #[serde(rename = "saveVersion", skip_serializing_if = "Option::is_none")]
+    pub save_version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i64>,
+    #[serde(rename = "mapStats", skip_serializing_if = "Option::is_none")]
+    pub map_stats: Option<MapStats>,
+    #[serde(rename = "worldLaws", skip_serializing_if = "Option::is_none")]
+    pub world_laws: Option<WorldLaws>,
+    /// Tile type ids referenced by index from `tile_array`.
+    #[serde(rename = "tileMap", skip_serializing_if = "Option::is_none")]
+    pub tile_map: Option<Vec<String>>,
+    /// Per row, indices into `tile_map`, each repeated by the matching
+    /// entry of `tile_amounts`.
+    #[serde(rename = "tileArray", skip_serializing_if = "Option::is_none")]
+    pub tile_array: Option<Vec<Vec<i64>>>,
+    #[serde(rename = "tileAmounts", skip_serializing_if = "Option::is_none")]
+    pub tile_amounts: Option<Vec<Vec<i64>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fire: Option<Value>,
+    #[serde(rename = "conwayEater", skip_serializing_if = "Option::is_none")]
+    pub conway_eater: Option<Value>,
+    #[serde(rename = "conwayCreator", skip_serializing_if = "Option::is_none")]
+    pub conway_creator: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frozen_tiles: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actors_data: Option<Vec<ActorData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buildings: Option<Vec<BuildingData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cities: Option<Vec<CityData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kingdoms: Option<Vec<KingdomData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cultures: Option<Vec<CultureData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clans: Option<Vec<ClanData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alliances: Option<Vec<AllianceData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wars: Option<Vec<WarData>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plots: Option<Vec<PlotData>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SavedMap {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn from_value(value: Value) -> serde_json::Result<Self> {
+        serde_json::from_value(value)
+    }
+
+    pub fn to_value(&self) -> serde_json::Result<Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Tile type ids row by row, expanded from the runs of `tile_array` and
+    /// `tile_amounts`. An index missing from `tile_map` gives `""`.
+    pub fn tiles(&self) -> Option<Vec<Vec<&str>>> {
+        let tile_map = self.tile_map.as_ref()?;
+        let array = self.tile_array.as_ref()?;
+        let amounts = self.tile_amounts.as_ref()?;
+        let rows = array
+            .iter()
+            .zip(amounts)
+            .map(|(indices, counts)| {
+                let mut row = Vec::new();
+                for (&index, &count) in indices.iter().zip(counts) {
+                    let tile = usize::try_from(index)
+                        .ok()
+                        .and_then(|i| tile_map.get(i))
+                        .map_or("", String::as_str);
+                    row.extend(std::iter::repeat_n(tile, count.max(0) as usize));
+                }
+                row
+            })
+            .collect();
+        Some(rows)
+    }
+
+    /// Replaces the tile sections with `rows` of tile type ids, run-length
+    /// encoded the way the game writes them.
+    pub fn set_tiles<S: AsRef<str>>(&mut self, rows: &[Vec<S>]) {
+        let mut tile_map: Vec<String> = Vec::new();
+        let mut array = Vec::with_capacity(rows.len());
+        let mut amounts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut indices: Vec<i64> = Vec::new();
+            let mut counts: Vec<i64> = Vec::new();
+            let mut previous = None;
+            for tile in row {
+                let tile = tile.as_ref();
+                if previous == Some(tile) {
+                    *counts.last_mut().unwrap() += 1;
+                    continue;
+                }
+                let index = match tile_map.iter().position(|t| t == tile) {
+                    Some(index) => index,
+                    None => {
+                        tile_map.push(tile.to_string());
+                        tile_map.len() - 1
+                    }
+                };
+                indices.push(index as i64);
+                counts.push(1);
+                previous = Some(tile);
+            }
+            array.push(indices);
+            amounts.push(counts);
+        }
+        self.tile_map = Some(tile_map);
+        self.tile_array = Some(array);
+        self.tile_amounts = Some(amounts);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// In-game time in seconds since the world was created.
+    #[serde(rename = "worldTime", skip_serializing_if = "Option::is_none")]
+    pub world_time: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldLaws {
+    pub list: Vec<WorldLaw>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl WorldLaws {
+    /// Value of the law `name`, `None` if the save does not list it.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        self.list.iter().find(|law| law.name == name)?.bool_val
+    }
+}
+
+/// Switches the law `name` on or off in the JSON of a whole map, adding it
+/// to `worldLaws` if needed. Nothing else in the map changes. Returns the
+/// previous value.
+pub fn set_law(map: &mut Value, name: &str, value: bool) -> Result<Option<bool>, LawError> {
+    let list = map
+        .as_object_mut()
+        .ok_or(LawError("the map is not a JSON object"))?
+        .entry("worldLaws")
+        .or_insert_with(|| json!({ "list": [] }))
+        .as_object_mut()
+        .ok_or(LawError("worldLaws is not an object"))?
+        .entry("list")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or(LawError("the list of worldLaws is not an array"))?;
+    let law = list
+        .iter_mut()
+        .find(|law| law.get("name").and_then(Value::as_str) == Some(name));
+    match law.and_then(Value::as_object_mut) {
+        Some(law) => {
+            let old = law.insert("boolVal".to_string(), Value::Bool(value));
+            Ok(old.and_then(|v| v.as_bool()))
+        }
+        None => {
+            list.push(json!({ "name": name, "boolVal": value }));
+            Ok(None)
+        }
+    }
+}
+
+/// Why [`set_law`] could not change a map: the section the laws are in has
+/// the wrong type.
+#[derive(Debug)]
+pub struct LawError(pub &'static str);
+
+impl fmt::Display for LawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot set a world law: {}", self.0)
+    }
+}
+
+impl std::error::Error for LawError {}
+
+/// A single law, such as `world_law_diplomacy`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldLaw {
+    pub name: String,
+    #[serde(rename = "boolVal", skip_serializing_if = "Option::is_none")]
+    pub bool_val: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Object id. Recent saves use numbers, older ones strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    Number(i64),
+    Text(String),
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Number(id) => write!(f, "{}", id),
+            Id::Text(id) => f.write_str(id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActorData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    /// Species, such as `human` or `sheep`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildingData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Common shape of the named objects of a save: cities, kingdoms,
+/// cultures, clans, alliances, wars and plots.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+pub type CityData = EntityData;
+pub type KingdomData = EntityData;
+pub type CultureData = EntityData;
+pub type ClanData = EntityData;
+pub type AllianceData = EntityData;
+pub type WarData = EntityData;
+pub type PlotData = EntityData;
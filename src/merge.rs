@@ -0,0 +1,439 @@
+//! Three-way merge of saves that were edited separately.
+//!
+//! Sections are merged as a whole, except for lists of objects with an `id`
+//! (actors, kingdoms, cities, ...), world laws, which are merged by name,
+//! and tiles, which are merged tile by tile. When both sides changed the
+//! same thing differently, our version is kept and a [`Conflict`] recorded.
+
+use crate::{diff::REGION_SIZE, map::SavedMap};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+const TILE_KEYS: [&str; 3] = ["tileMap", "tileArray", "tileAmounts"];
+
+/// Merged map and the conflicts that were resolved with our version.
+pub struct Merge {
+    pub value: Value,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Something both sides changed differently.
+#[derive(Debug)]
+pub struct Conflict {
+    pub section: String,
+    /// Object, law or tile region within the section, if the section was
+    /// not merged as a whole.
+    pub item: Option<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.item {
+            Some(item) => write!(f, "{}: {}", self.section, item),
+            None => f.write_str(&self.section),
+        }
+    }
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Merge {
+    let mut conflicts = Vec::new();
+    let (Value::Object(base), Value::Object(ours), Value::Object(theirs)) = (base, ours, theirs)
+    else {
+        let value = merge_whole("map", Some(base), Some(ours), Some(theirs), &mut conflicts);
+        return Merge {
+            value: value.unwrap_or(Value::Null),
+            conflicts,
+        };
+    };
+
+    let mut tiles = merge_tiles(base, ours, theirs, &mut conflicts);
+    let mut merged = Map::new();
+    let keys = ours.keys().chain(theirs.keys().filter(|k| !ours.contains_key(*k)));
+    for key in keys {
+        let (b, o, t) = (base.get(key), ours.get(key), theirs.get(key));
+        let value = if TILE_KEYS.contains(&key.as_str()) {
+            tiles.remove(key.as_str()).flatten()
+        } else if key == "worldLaws" {
+            merge_laws(b, o, t, &mut conflicts)
+        } else {
+            merge_section(key, b, o, t, &mut conflicts)
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Merge {
+        value: Value::Object(merged),
+        conflicts,
+    }
+}
+
+/// The side that changed, or `None` when both changed differently.
+fn pick<'a>(
+    base: Option<&'a Value>,
+    ours: Option<&'a Value>,
+    theirs: Option<&'a Value>,
+) -> Option<Option<&'a Value>> {
+    if ours == theirs || theirs == base {
+        Some(ours)
+    } else if ours == base {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+fn merge_whole(
+    section: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    match pick(base, ours, theirs) {
+        Some(value) => value.cloned(),
+        None => {
+            conflicts.push(Conflict {
+                section: section.to_string(),
+                item: None,
+            });
+            ours.or(theirs).cloned()
+        }
+    }
+}
+
+/// Merges lists whose objects all have an `id` object by object, and any
+/// other value as a whole.
+fn merge_section(
+    section: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    // A section added on both sides is merged as if it was empty before.
+    let base_list = base.map_or(Some(&[][..]), |v| keyed_list(v, "id"));
+    let lists = [ours, theirs].map(|v| v.and_then(|v| keyed_list(v, "id")));
+    match (base_list, lists) {
+        (Some(b), [Some(o), Some(t)]) => {
+            Some(Value::Array(merge_list(section, "id", b, o, t, conflicts)))
+        }
+        _ => merge_whole(section, base, ours, theirs, conflicts),
+    }
+}
+
+/// Merges the law list by law name and the rest of `worldLaws` as a whole.
+fn merge_laws(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    let base_list = base.map_or(Some(&[][..]), law_list);
+    let (Some(Value::Object(laws)), Some(b), Some(o), Some(t)) =
+        (ours, base_list, ours.and_then(law_list), theirs.and_then(law_list))
+    else {
+        return merge_whole("worldLaws", base, ours, theirs, conflicts);
+    };
+    let without_list = |v: Option<&Value>| {
+        let mut v = v.cloned();
+        if let Some(Value::Object(object)) = &mut v {
+            object.remove("list");
+        }
+        v
+    };
+    let rest = merge_whole(
+        "worldLaws",
+        without_list(base).as_ref(),
+        without_list(ours).as_ref(),
+        without_list(theirs).as_ref(),
+        conflicts,
+    );
+
+    let mut merged = Map::new();
+    for key in laws.keys() {
+        if key == "list" {
+            let list = merge_list("worldLaws", "name", b, o, t, conflicts);
+            merged.insert(key.clone(), Value::Array(list));
+        } else if let Some(value) = rest.as_ref().and_then(|r| r.get(key)) {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    if let Some(Value::Object(rest)) = rest {
+        for (key, value) in rest {
+            merged.entry(key).or_insert(value);
+        }
+    }
+    Some(Value::Object(merged))
+}
+
+fn law_list(laws: &Value) -> Option<&[Value]> {
+    keyed_list(laws.get("list")?, "name")
+}
+
+/// The elements of `value` if it is a list of objects that all have `key`.
+fn keyed_list<'a>(value: &'a Value, key: &str) -> Option<&'a [Value]> {
+    let list = value.as_array()?;
+    list.iter().all(|v| v.get(key).is_some()).then_some(list.as_slice())
+}
+
+fn merge_list<'a>(
+    section: &str,
+    key: &str,
+    base: &'a [Value],
+    ours: &'a [Value],
+    theirs: &'a [Value],
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<Value> {
+    let [base_index, our_index, their_index] = [base, ours, theirs].map(|list| index(list, key));
+    let ids = ours
+        .iter()
+        .chain(theirs.iter().filter(|t| !our_index.contains_key(&t[key].to_string())))
+        .map(|v| &v[key]);
+
+    let mut merged = Vec::new();
+    for id in ids {
+        let text = id.to_string();
+        let find = |list: &'a [Value], index: &HashMap<String, usize>| {
+            index.get(&text).map(|&i| &list[i])
+        };
+        let b = find(base, &base_index);
+        let o = find(ours, &our_index);
+        let t = find(theirs, &their_index);
+        match pick(b, o, t) {
+            Some(value) => merged.extend(value.cloned()),
+            None => {
+                conflicts.push(Conflict {
+                    section: section.to_string(),
+                    item: Some(describe(key, id, o.or(t))),
+                });
+                merged.extend(o.or(t).cloned());
+            }
+        }
+    }
+    merged
+}
+
+/// Position of the first object with each value of `key`, by the value's
+/// JSON text.
+fn index(list: &[Value], key: &str) -> HashMap<String, usize> {
+    let mut index = HashMap::with_capacity(list.len());
+    for (i, value) in list.iter().enumerate() {
+        index.entry(value[key].to_string()).or_insert(i);
+    }
+    index
+}
+
+fn describe(key: &str, id: &Value, value: Option<&Value>) -> String {
+    let id = match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if key != "id" {
+        return id;
+    }
+    match value.and_then(|v| v.get("name")).and_then(Value::as_str) {
+        Some(name) => format!("{} (id {})", name, id),
+        None => format!("id {}", id),
+    }
+}
+
+/// Merged values of the tile sections by key; `None` drops the section.
+fn merge_tiles(
+    base: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> BTreeMap<&'static str, Option<Value>> {
+    let sections = |map: &Map<String, Value>| TILE_KEYS.map(|key| map.get(key).cloned());
+    let (b, o, t) = (sections(base), sections(ours), sections(theirs));
+    let keep = |values: [Option<Value>; 3]| TILE_KEYS.into_iter().zip(values).collect();
+    if o == t || t == b {
+        return keep(o);
+    }
+    if o == b {
+        return keep(t);
+    }
+
+    let saved = |[tile_map, tile_array, tile_amounts]: &[Option<Value>; 3]| SavedMap {
+        tile_map: tile_map.clone().and_then(|v| serde_json::from_value(v).ok()),
+        tile_array: tile_array.clone().and_then(|v| serde_json::from_value(v).ok()),
+        tile_amounts: tile_amounts.clone().and_then(|v| serde_json::from_value(v).ok()),
+        ..SavedMap::default()
+    };
+    let (base_map, our_map, their_map) = (saved(&b), saved(&o), saved(&t));
+    let grids = (base_map.tiles(), our_map.tiles(), their_map.tiles());
+    let (Some(base_rows), Some(mut rows), Some(their_rows)) = grids else {
+        conflicts.push(Conflict {
+            section: "tiles".to_string(),
+            item: None,
+        });
+        return keep(o);
+    };
+    let shape = |rows: &[Vec<&str>]| rows.iter().map(Vec::len).collect::<Vec<_>>();
+    if shape(&base_rows) != shape(&rows) || shape(&rows) != shape(&their_rows) {
+        conflicts.push(Conflict {
+            section: "tiles".to_string(),
+            item: Some("world size differs".to_string()),
+        });
+        return keep(o);
+    }
+
+    let mut regions: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (x, tile) in row.iter_mut().enumerate() {
+            let (base_tile, their_tile) = (base_rows[y][x], their_rows[y][x]);
+            if *tile == base_tile {
+                *tile = their_tile;
+            } else if their_tile != base_tile && their_tile != *tile {
+                *regions.entry((y / REGION_SIZE, x / REGION_SIZE)).or_default() += 1;
+            }
+        }
+    }
+    for ((row, column), count) in regions {
+        let (x, y) = (column * REGION_SIZE, row * REGION_SIZE);
+        conflicts.push(Conflict {
+            section: "tiles".to_string(),
+            item: Some(format!(
+                "x {}-{}, y {}-{} ({} tiles)",
+                x,
+                x + REGION_SIZE - 1,
+                y,
+                y + REGION_SIZE - 1,
+                count
+            )),
+        });
+    }
+
+    let mut merged = SavedMap::default();
+    merged.set_tiles(&rows);
+    keep([
+        merged.tile_map.map(Value::from),
+        merged.tile_array.map(Value::from),
+        merged.tile_amounts.map(Value::from),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn actors(actors: Value) -> Value {
+        json!({ "saveVersion": 15, "actors_data": actors })
+    }
+
+    /// A map whose tiles are given by one character per tile.
+    fn tiles(rows: &[&str]) -> Value {
+        let rows: Vec<Vec<String>> =
+            rows.iter().map(|row| row.chars().map(String::from).collect()).collect();
+        let mut map = SavedMap::default();
+        map.set_tiles(&rows);
+        map.to_value().unwrap()
+    }
+
+    fn tile_rows(value: &Value) -> Vec<String> {
+        let map = SavedMap::from_value(value.clone()).unwrap();
+        map.tiles().unwrap().iter().map(|row| row.concat()).collect()
+    }
+
+    #[test]
+    fn merges_changes_to_different_ids() {
+        let base = actors(json!([{ "id": 1, "x": 0 }, { "id": 2, "x": 0 }, { "id": 3, "x": 0 }]));
+        let ours = actors(json!([{ "id": 1, "x": 5 }, { "id": 2, "x": 0 }, { "id": 3, "x": 0 }]));
+        let theirs = actors(json!([
+            { "id": 1, "x": 0 },
+            { "id": 2, "x": 7 },
+            { "id": 4, "x": 1 }
+        ]));
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(
+            merged.value["actors_data"],
+            json!([{ "id": 1, "x": 5 }, { "id": 2, "x": 7 }, { "id": 4, "x": 1 }])
+        );
+    }
+
+    #[test]
+    fn keeps_ours_when_both_change_an_id() {
+        let base = actors(json!([{ "id": 1, "name": "Bob", "x": 0 }, { "id": 2, "x": 0 }]));
+        let ours = actors(json!([{ "id": 1, "name": "Bob", "x": 5 }, { "id": 2, "x": 0 }]));
+        let theirs = actors(json!([{ "id": 1, "name": "Bob", "x": 9 }, { "id": 2, "x": 3 }]));
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(
+            merged.value["actors_data"],
+            json!([{ "id": 1, "name": "Bob", "x": 5 }, { "id": 2, "x": 3 }])
+        );
+        let conflicts: Vec<String> = merged.conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(conflicts, ["actors_data: Bob (id 1)"]);
+    }
+
+    #[test]
+    fn deletion_on_one_side_conflicts_with_a_change_on_the_other() {
+        let base = actors(json!([{ "id": 1, "x": 0 }]));
+        let ours = actors(json!([]));
+        let theirs = actors(json!([{ "id": 1, "x": 4 }]));
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.value["actors_data"], json!([{ "id": 1, "x": 4 }]));
+        assert_eq!(merged.conflicts.len(), 1);
+
+        let merged = merge(&base, &ours, &base);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.value["actors_data"], json!([]));
+    }
+
+    #[test]
+    fn string_and_number_ids_are_different_ids() {
+        let base = actors(json!([]));
+        let ours = actors(json!([{ "id": 1 }]));
+        let theirs = actors(json!([{ "id": "1" }]));
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.value["actors_data"], json!([{ "id": 1 }, { "id": "1" }]));
+    }
+
+    #[test]
+    fn merges_laws_by_name() {
+        let laws = |diplomacy: bool, hunger: bool| {
+            json!({ "worldLaws": { "list": [
+                { "name": "world_law_diplomacy", "boolVal": diplomacy },
+                { "name": "world_law_hunger", "boolVal": hunger }
+            ]}})
+        };
+        let merged = merge(&laws(true, true), &laws(false, true), &laws(true, false));
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.value, laws(false, false));
+    }
+
+    #[test]
+    fn merges_tiles_changed_in_different_places() {
+        let base = tiles(&["aaaa", "aaaa"]);
+        let ours = tiles(&["baaa", "aaaa"]);
+        let theirs = tiles(&["aaaa", "aaac"]);
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(tile_rows(&merged.value), ["baaa", "aaac"]);
+    }
+
+    #[test]
+    fn keeps_our_tile_when_both_change_it() {
+        let base = tiles(&["aaaa", "aaaa"]);
+        let ours = tiles(&["baaa", "aaaa"]);
+        let theirs = tiles(&["caaa", "aaac"]);
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(tile_rows(&merged.value), ["baaa", "aaac"]);
+        let conflicts: Vec<String> = merged.conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(conflicts, ["tiles: x 0-63, y 0-63 (1 tiles)"]);
+    }
+
+    #[test]
+    fn different_world_sizes_conflict() {
+        let merged = merge(&tiles(&["aa"]), &tiles(&["ab"]), &tiles(&["aa", "aa"]));
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(tile_rows(&merged.value), ["ab"]);
+    }
+}
@@ -0,0 +1,240 @@
+//! The `map.meta` file of a save slot: the summary the game shows in its
+//! load menu without reading the whole map.
+//!
+//! Like [`crate::map`], only the fields the tools work with are typed and
+//! everything else is kept in `extra`.
+
+use crate::{
+    atomic, backup, map::SavedMap, Encoding, Map, PressorError, Result, WriteOptions,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i64>,
+    /// Number of actors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub population: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cities: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kingdoms: Option<u64>,
+    #[serde(rename = "saveTime", skip_serializing_if = "Option::is_none")]
+    pub save_time: Option<SaveTime>,
+    #[serde(rename = "gameVersion", skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+    #[serde(rename = "saveVersion", skip_serializing_if = "Option::is_none")]
+    pub save_version: Option<i64>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+    /// Whether the file was compressed. It is written back the same way,
+    /// as zlib.
+    #[serde(skip)]
+    pub compressed: bool,
+}
+
+/// When the slot was saved, as Unix seconds or as text. Updating keeps the
+/// form the file already used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SaveTime {
+    Unix(i64),
+    Text(String),
+}
+
+impl SaveTime {
+    /// `time` in the same form as `self`: Unix seconds, or text such as
+    /// `2026-10-17T12:00:00Z`.
+    fn at(&self, time: SystemTime) -> Self {
+        match self {
+            SaveTime::Unix(_) => {
+                let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                SaveTime::Unix(secs as i64)
+            }
+            SaveTime::Text(_) => {
+                let (year, month, day, hour, minute, second) = backup::utc(time);
+                SaveTime::Text(format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                    year, month, day, hour, minute, second
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SaveTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveTime::Unix(secs) => write!(f, "{}", secs),
+            SaveTime::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+impl MapMeta {
+    /// Parses a `map.meta` file, compressed or plain JSON.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let map = Map::from_bytes(data)?;
+        let meta = serde_json::from_value(map.to_value()?)?;
+        Ok(Self {
+            compressed: map.format().is_compressed(),
+            ..meta
+        })
+    }
+
+    /// A summary of `map`, as [`MapMeta::update`] fills it in.
+    pub fn from_saved_map(map: &SavedMap) -> Self {
+        let mut meta = Self::default();
+        meta.update(map, SystemTime::now());
+        meta
+    }
+
+    /// Sets every field that can be taken from `map` and the save time to
+    /// `now`. The name and description are kept if the map has none, and
+    /// the game version and unknown fields are kept as they are.
+    pub fn update(&mut self, map: &SavedMap, now: SystemTime) {
+        let stats = map.map_stats.as_ref();
+        if let Some(name) = stats.and_then(|s| s.name.clone()) {
+            self.name = Some(name);
+        }
+        if let Some(description) = stats.and_then(|s| s.description.clone()) {
+            self.description = Some(description);
+        }
+        self.width = map.width;
+        self.height = map.height;
+        self.population = Some(count(&map.actors_data));
+        self.cities = Some(count(&map.cities));
+        self.kingdoms = Some(count(&map.kingdoms));
+        self.save_version = map.save_version;
+        let form = self.save_time.take().unwrap_or(SaveTime::Text(String::new()));
+        self.save_time = Some(form.at(now));
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Writes the metadata as JSON, compressed if it was read compressed,
+    /// replacing `path` atomically.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = self.to_json()?;
+        let data = if self.compressed {
+            let options = WriteOptions {
+                validate: false,
+                ..WriteOptions::default()
+            };
+            Map::from_json(json).to_bytes(Encoding::Compressed, &options)?.0
+        } else {
+            json.into_bytes()
+        };
+        atomic::write(path, &data)
+            .map_err(|e| PressorError::io(format!("File writing error in {}", path.display()), e))
+    }
+}
+
+fn count<T>(list: &Option<Vec<T>>) -> u64 {
+    list.as_ref().map_or(0, Vec::len) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{detect_format, Format};
+    use serde_json::json;
+    use std::{fs, time::Duration};
+
+    const META: &str = r#"{"name":"Old","width":64,"saveTime":1700000000,"mods":["x"]}"#;
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_792_240_496)
+    }
+
+    fn saved_map() -> SavedMap {
+        SavedMap::from_value(json!({
+            "saveVersion": 15,
+            "width": 128,
+            "height": 96,
+            "mapStats": { "description": "A world" },
+            "actors_data": [{ "id": 1 }, { "id": 2 }, { "id": 3 }],
+            "cities": [{ "id": 1 }],
+            "kingdoms": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_plain_and_compressed_meta() {
+        let plain = MapMeta::from_bytes(META.as_bytes()).unwrap();
+        assert_eq!(plain.name.as_deref(), Some("Old"));
+        assert_eq!(plain.save_time, Some(SaveTime::Unix(1_700_000_000)));
+        assert_eq!(plain.extra["mods"], json!(["x"]));
+        assert!(!plain.compressed);
+
+        let options = WriteOptions::default();
+        let (data, _) = Map::from_json(META).to_bytes(Encoding::Compressed, &options).unwrap();
+        let compressed = MapMeta::from_bytes(&data).unwrap();
+        assert!(compressed.compressed);
+        assert_eq!(compressed, MapMeta { compressed: true, ..plain });
+    }
+
+    #[test]
+    fn update_takes_what_the_map_has() {
+        let mut meta = MapMeta::from_bytes(META.as_bytes()).unwrap();
+        meta.update(&saved_map(), now());
+        assert_eq!(meta.name.as_deref(), Some("Old"));
+        assert_eq!(meta.description.as_deref(), Some("A world"));
+        assert_eq!((meta.width, meta.height), (Some(128), Some(96)));
+        assert_eq!(
+            (meta.population, meta.cities, meta.kingdoms),
+            (Some(3), Some(1), Some(0))
+        );
+        assert_eq!(meta.save_version, Some(15));
+        assert_eq!(meta.save_time, Some(SaveTime::Unix(1_792_240_496)));
+        assert_eq!(meta.extra["mods"], json!(["x"]));
+    }
+
+    #[test]
+    fn save_time_keeps_its_form() {
+        let mut meta = MapMeta::from_bytes(br#"{"saveTime":"2020-01-01T00:00:00Z"}"#).unwrap();
+        meta.update(&saved_map(), now());
+        assert_eq!(meta.save_time, Some(SaveTime::Text("2026-10-17T12:34:56Z".to_string())));
+
+        // Without a save time, text is written.
+        let mut meta = MapMeta::default();
+        meta.update(&SavedMap::default(), now());
+        assert_eq!(meta.save_time.unwrap().to_string(), "2026-10-17T12:34:56Z");
+    }
+
+    #[test]
+    fn writes_back_in_the_same_encoding() {
+        let dir = std::env::temp_dir().join(format!("pressor-meta-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("map.meta");
+        let mut meta = MapMeta::from_bytes(META.as_bytes()).unwrap();
+
+        meta.write(&path).unwrap();
+        let plain = fs::read(&path).unwrap();
+        meta.compressed = true;
+        meta.write(&path).unwrap();
+        let compressed = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(String::from_utf8(plain).unwrap(), META);
+        assert_eq!(detect_format(&compressed), Format::ZlibJson);
+        assert_eq!(MapMeta::from_bytes(&compressed).unwrap(), meta);
+    }
+}
@@ -0,0 +1,386 @@
+//! JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7396) for maps.
+
+use serde_json::{json, Map, Number, Value};
+use std::fmt;
+
+/// Why a patch could not be applied.
+#[derive(Debug)]
+pub struct PatchError {
+    /// Index of the failed operation in the patch.
+    pub operation: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Patch operation {} failed: {}", self.operation, self.message)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Kind of patch, told apart by its top-level value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchKind {
+    /// A list of operations.
+    JsonPatch,
+    /// An object merged into the document.
+    MergePatch,
+}
+
+/// Applies a JSON Patch if `patch` is a list, or else a merge patch, to
+/// `target`. A JSON Patch is applied completely or not at all.
+pub fn apply(target: &mut Value, patch: &Value) -> Result<PatchKind, PatchError> {
+    match patch {
+        Value::Array(operations) => {
+            let mut patched = target.clone();
+            for (index, operation) in operations.iter().enumerate() {
+                apply_operation(&mut patched, operation).map_err(|message| PatchError {
+                    operation: index,
+                    message,
+                })?;
+            }
+            *target = patched;
+            Ok(PatchKind::JsonPatch)
+        }
+        _ => {
+            merge_patch(target, patch);
+            Ok(PatchKind::MergePatch)
+        }
+    }
+}
+
+/// Applies a merge patch: `null` removes a member, objects are merged
+/// recursively and anything else replaces the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(object) = target else {
+        unreachable!();
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            object.shift_remove(key);
+        } else {
+            merge_patch(object.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+fn apply_operation(target: &mut Value, operation: &Value) -> Result<(), String> {
+    let field = |name: &str| operation.get(name).ok_or_else(|| format!("missing \"{}\"", name));
+    let text = |name: &str| {
+        field(name)?
+            .as_str()
+            .ok_or_else(|| format!("\"{}\" must be a string", name))
+    };
+    let op = text("op")?;
+    let pointer = text("path")?;
+    let path = parse_pointer(pointer)?;
+
+    match op {
+        "add" => add(target, &path, field("value")?.clone()),
+        "remove" => remove(target, &path).map(|_| ()),
+        "replace" => {
+            let slot = lookup_mut(target, &path)?;
+            *slot = field("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = parse_pointer(text("from")?)?;
+            if path.len() > from.len() && path[..from.len()] == from[..] {
+                return Err("cannot move a value into itself".to_string());
+            }
+            let value = remove(target, &from)?;
+            add(target, &path, value)
+        }
+        "copy" => {
+            let from = parse_pointer(text("from")?)?;
+            let value = lookup(target, &from)?.clone();
+            add(target, &path, value)
+        }
+        "test" => {
+            if json_equal(lookup(target, &path)?, field("value")?) {
+                Ok(())
+            } else {
+                Err("the value differs".to_string())
+            }
+        }
+        other => Err(format!("unknown operation \"{}\"", other)),
+    }
+    .map_err(|message| format!("{} \"{}\": {}", op, pointer, message))
+}
+
+/// Equality as `test` defines it: numbers are equal when their values are,
+/// so `1` equals `1.0`, and arrays and objects when their elements are.
+fn json_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => number_equal(x, y),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| json_equal(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter().all(|(key, x)| y.get(key).is_some_and(|y| json_equal(x, y)))
+        }
+        _ => a == b,
+    }
+}
+
+fn number_equal(a: &Number, b: &Number) -> bool {
+    if let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) {
+        return x == y;
+    }
+    if let (Some(x), Some(y)) = (a.as_u64(), b.as_u64()) {
+        return x == y;
+    }
+    a.as_f64() == b.as_f64()
+}
+
+/// Splits a JSON Pointer (RFC 6901) into unescaped reference tokens.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(format!("invalid pointer \"{}\", expected it to start with /", pointer));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    if allow_end && token == "-" {
+        return Ok(len);
+    }
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && (index < len || (allow_end && index == len)) => Ok(index),
+        Ok(_) if valid => Err(format!("index {} is out of bounds", token)),
+        _ => Err(format!("invalid array index \"{}\"", token)),
+    }
+}
+
+fn lookup<'a>(target: &'a Value, path: &[String]) -> Result<&'a Value, String> {
+    path.iter().try_fold(target, |value, token| match value {
+        Value::Object(object) => {
+            object.get(token).ok_or_else(|| format!("no member \"{}\"", token))
+        }
+        Value::Array(list) => Ok(&list[array_index(token, list.len(), false)?]),
+        _ => Err(format!("cannot look up \"{}\" in a scalar", token)),
+    })
+}
+
+fn lookup_mut<'a>(target: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    path.iter().try_fold(target, |value, token| match value {
+        Value::Object(object) => {
+            object.get_mut(token).ok_or_else(|| format!("no member \"{}\"", token))
+        }
+        Value::Array(list) => {
+            let index = array_index(token, list.len(), false)?;
+            Ok(&mut list[index])
+        }
+        _ => Err(format!("cannot look up \"{}\" in a scalar", token)),
+    })
+}
+
+fn add(target: &mut Value, path: &[String], value: Value) -> Result<(), String> {
+    let Some((last, parent)) = path.split_last() else {
+        *target = value;
+        return Ok(());
+    };
+    match lookup_mut(target, parent)? {
+        Value::Object(object) => {
+            object.insert(last.clone(), value);
+            Ok(())
+        }
+        Value::Array(list) => {
+            let index = array_index(last, list.len(), true)?;
+            list.insert(index, value);
+            Ok(())
+        }
+        _ => Err(format!("cannot add \"{}\" to a scalar", last)),
+    }
+}
+
+fn remove(target: &mut Value, path: &[String]) -> Result<Value, String> {
+    let Some((last, parent)) = path.split_last() else {
+        return Err("cannot remove the whole document".to_string());
+    };
+    match lookup_mut(target, parent)? {
+        Value::Object(object) => object
+            .shift_remove(last)
+            .ok_or_else(|| format!("no member \"{}\"", last)),
+        Value::Array(list) => {
+            let index = array_index(last, list.len(), false)?;
+            Ok(list.remove(index))
+        }
+        _ => Err(format!("cannot remove \"{}\" from a scalar", last)),
+    }
+}
+
+/// A JSON Patch that turns `old` into `new`. Lists are compared after
+/// skipping their common start and end, so inserting or removing a few
+/// elements gives a few operations.
+pub fn diff(old: &Value, new: &Value) -> Vec<Value> {
+    let mut operations = Vec::new();
+    diff_into(old, new, "", &mut operations);
+    operations
+}
+
+fn diff_into(old: &Value, new: &Value, path: &str, operations: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let child = format!("{}/{}", path, escape(key));
+                match b.get(key) {
+                    Some(other) => diff_into(value, other, &child, operations),
+                    None => operations.push(json!({"op": "remove", "path": child})),
+                }
+            }
+            for (key, value) in b {
+                if !a.contains_key(key) {
+                    let child = format!("{}/{}", path, escape(key));
+                    operations.push(json!({"op": "add", "path": child, "value": value}));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+            let suffix = a[prefix..]
+                .iter()
+                .rev()
+                .zip(b[prefix..].iter().rev())
+                .take_while(|(x, y)| x == y)
+                .count();
+            let old_middle = &a[prefix..a.len() - suffix];
+            let new_middle = &b[prefix..b.len() - suffix];
+            let common = old_middle.len().min(new_middle.len());
+            for i in 0..common {
+                let child = format!("{}/{}", path, prefix + i);
+                diff_into(&old_middle[i], &new_middle[i], &child, operations);
+            }
+            for _ in common..old_middle.len() {
+                let child = format!("{}/{}", path, prefix + common);
+                operations.push(json!({"op": "remove", "path": child}));
+            }
+            for (i, value) in new_middle.iter().enumerate().skip(common) {
+                let child = format!("{}/{}", path, prefix + i);
+                operations.push(json!({"op": "add", "path": child, "value": value}));
+            }
+        }
+        _ if old != new => {
+            operations.push(json!({"op": "replace", "path": path, "value": new}));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patched(target: Value, patch: Value) -> Result<Value, PatchError> {
+        let mut target = target;
+        apply(&mut target, &patch)?;
+        Ok(target)
+    }
+
+    fn round_trip(old: Value, new: Value) {
+        let patch = Value::Array(diff(&old, &new));
+        assert_eq!(patched(old, patch.clone()).unwrap(), new, "patch: {}", patch);
+    }
+
+    #[test]
+    fn applying_a_diff_gives_the_new_value() {
+        round_trip(json!({ "a": 1, "b": 2 }), json!({ "a": 1, "b": 3, "c": 4 }));
+        round_trip(json!({ "a": { "b": [1, 2] } }), json!({ "a": {} }));
+        round_trip(json!([1, 2, 3, 4]), json!([1, 5, 6, 3, 4]));
+        round_trip(json!([1, 2, 3, 4]), json!([1, 4]));
+        round_trip(json!([{ "id": 1 }, { "id": 2 }]), json!([{ "id": 1, "x": 0 }, { "id": 2 }]));
+        round_trip(json!({ "a/b": 1, "c~d": 2 }), json!({ "a/b": 2 }));
+        round_trip(json!({ "a": 1 }), json!([1]));
+        round_trip(json!([]), json!([null, []]));
+    }
+
+    #[test]
+    fn diff_of_a_small_change_is_small() {
+        let old = json!({ "list": [1, 2, 3, 4, 5] });
+        let new = json!({ "list": [1, 2, 9, 3, 4, 5] });
+        assert_eq!(diff(&old, &new), [json!({ "op": "add", "path": "/list/2", "value": 9 })]);
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn dash_appends_to_a_list() {
+        let patch = json!([{ "op": "add", "path": "/list/-", "value": 3 }]);
+        let list = patched(json!({ "list": [1, 2] }), patch).unwrap();
+        assert_eq!(list, json!({ "list": [1, 2, 3] }));
+        let patch = json!([{ "op": "remove", "path": "/list/-" }]);
+        assert!(patched(json!({ "list": [1] }), patch).is_err());
+        let patch = json!([{ "op": "add", "path": "/list/3", "value": 0 }]);
+        assert!(patched(json!({ "list": [1] }), patch).is_err());
+    }
+
+    #[test]
+    fn cannot_move_a_value_into_itself() {
+        let patch = json!([{ "op": "move", "from": "/a", "path": "/a/b" }]);
+        let error = patched(json!({ "a": { "b": 1 } }), patch).unwrap_err();
+        assert!(error.message.contains("into itself"), "{}", error);
+
+        let patch = json!([{ "op": "move", "from": "/a", "path": "/ab" }]);
+        assert_eq!(patched(json!({ "a": 1 }), patch).unwrap(), json!({ "ab": 1 }));
+    }
+
+    #[test]
+    fn test_compares_numbers_by_value() {
+        let target = json!({ "x": 1.0, "list": [1, { "y": 2.0 }] });
+        let patch = json!([
+            { "op": "test", "path": "/x", "value": 1 },
+            { "op": "test", "path": "/list", "value": [1.0, { "y": 2 }] }
+        ]);
+        assert!(patched(target.clone(), patch).is_ok());
+        let patch = json!([{ "op": "test", "path": "/x", "value": "1" }]);
+        assert!(patched(target, patch).is_err());
+    }
+
+    #[test]
+    fn a_failed_patch_changes_nothing() {
+        let mut target = json!({ "a": 1 });
+        let patch = json!([
+            { "op": "replace", "path": "/a", "value": 2 },
+            { "op": "remove", "path": "/missing" }
+        ]);
+        let error = apply(&mut target, &patch).unwrap_err();
+        assert_eq!(error.operation, 1);
+        assert_eq!(target, json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn pointers_are_unescaped() {
+        let patch = json!([{ "op": "copy", "from": "/a~1b", "path": "/c~0d" }]);
+        assert_eq!(patched(json!({ "a/b": 1 }), patch).unwrap(), json!({ "a/b": 1, "c~d": 1 }));
+        let patch = json!([{ "op": "remove", "path": "a" }]);
+        assert!(patched(json!({ "a": 1 }), patch).is_err());
+    }
+
+    #[test]
+    fn merge_patch_removes_nulls_and_merges_objects() {
+        let target = json!({ "a": { "b": 1, "c": 2 }, "d": [1] });
+        let patch = json!({ "a": { "b": null, "e": 3 }, "d": [2] });
+        assert_eq!(patched(target, patch).unwrap(), json!({ "a": { "c": 2, "e": 3 }, "d": [2] }));
+    }
+}
@@ -0,0 +1,426 @@
+//! A subset of JSONPath for picking values out of a map.
+//!
+//! Supported are field access (`.name`, `['name']`), indices (`[0]`, `[-1]`
+//! for the last element), wildcards (`.*`, `[*]`) and filters comparing a
+//! field of each element with a literal, such as
+//! `actors_data[?(@.asset_id=="human")]` or `cities[?(@.age>=100)]`.
+//! A filter without a comparison, `[?(@.name)]`, keeps elements that have
+//! the field. The leading `$` may be left out.
+
+use serde_json::Value;
+use std::{cmp::Ordering, fmt};
+
+/// A parsed query.
+#[derive(Debug, Clone)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Field(String),
+    Index(i64),
+    Wildcard,
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    /// Fields below `@`.
+    path: Vec<String>,
+    comparison: Option<(Op, Value)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Why a query could not be parsed.
+#[derive(Debug)]
+pub struct QueryError {
+    /// Character position in the query.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid query at character {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+        .query()
+    }
+
+    /// Every value of `root` the query matches, in document order.
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for value in current {
+                match segment {
+                    Segment::Field(name) => next.extend(value.get(name)),
+                    Segment::Index(index) => {
+                        let list = value.as_array().map_or(&[][..], Vec::as_slice);
+                        let index = if *index < 0 {
+                            list.len().checked_sub(index.unsigned_abs() as usize)
+                        } else {
+                            Some(*index as usize)
+                        };
+                        next.extend(index.and_then(|i| list.get(i)));
+                    }
+                    Segment::Wildcard => next.extend(children(value)),
+                    Segment::Filter(filter) => {
+                        next.extend(children(value).filter(|child| filter.matches(child)))
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+fn children(value: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match value {
+        Value::Array(list) => Box::new(list.iter()),
+        Value::Object(object) => Box::new(object.values()),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+impl Filter {
+    fn matches(&self, item: &Value) -> bool {
+        let Some(field) = self.path.iter().try_fold(item, |value, name| value.get(name)) else {
+            return false;
+        };
+        let Some((op, literal)) = &self.comparison else {
+            return true;
+        };
+        let ordering = match (field, literal) {
+            (Value::Number(a), Value::Number(b)) => {
+                a.as_f64().zip(b.as_f64()).and_then(|(a, b)| a.partial_cmp(&b))
+            }
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) => (a == b).then_some(Ordering::Equal),
+        };
+        match op {
+            Op::Eq => ordering == Some(Ordering::Equal),
+            Op::Ne => ordering != Some(Ordering::Equal),
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, QueryError> {
+        Err(QueryError {
+            position: self.pos,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", c))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn query(mut self) -> Result<Query, QueryError> {
+        let mut segments = Vec::new();
+        self.skip_spaces();
+        if !self.eat('$') && !matches!(self.peek(), Some('.' | '[') | None) {
+            segments.push(Segment::Field(self.name()?));
+        }
+        loop {
+            match self.peek() {
+                None => break,
+                Some('.') => {
+                    self.pos += 1;
+                    if self.eat('*') {
+                        segments.push(Segment::Wildcard);
+                    } else {
+                        segments.push(Segment::Field(self.name()?));
+                    }
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    segments.push(self.bracket()?);
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.skip_spaces();
+                    if self.peek().is_some() {
+                        return self.error("unexpected space");
+                    }
+                }
+                Some(c) => return self.error(format!("unexpected '{}'", c)),
+            }
+        }
+        Ok(Query { segments })
+    }
+
+    /// A field name in dot notation.
+    fn name(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !matches!(c, '.' | '[' | ']' | '(' | ')' | '=' | '!' | '<' | '>')
+                && !c.is_whitespace())
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return self.error("expected a field name");
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// The part of a `[...]` segment after the opening bracket.
+    fn bracket(&mut self) -> Result<Segment, QueryError> {
+        self.skip_spaces();
+        let segment = match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Segment::Wildcard
+            }
+            Some('\'' | '"') => Segment::Field(self.string()?),
+            Some('?') => {
+                self.pos += 1;
+                Segment::Filter(self.filter()?)
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                let digits: String = self.chars[start..self.pos].iter().collect();
+                match digits.parse() {
+                    Ok(index) => Segment::Index(index),
+                    Err(_) => {
+                        self.pos = start;
+                        return self.error("invalid index");
+                    }
+                }
+            }
+            _ => return self.error("expected an index, a quoted name, '*' or a filter"),
+        };
+        self.skip_spaces();
+        self.expect(']')?;
+        Ok(segment)
+    }
+
+    fn filter(&mut self) -> Result<Filter, QueryError> {
+        self.expect('(')?;
+        self.skip_spaces();
+        self.expect('@')?;
+        let mut path = Vec::new();
+        loop {
+            if self.eat('.') {
+                path.push(self.name()?);
+            } else if self.peek() == Some('[')
+                && matches!(self.chars.get(self.pos + 1), Some('\'' | '"'))
+            {
+                self.pos += 1;
+                path.push(self.string()?);
+                self.expect(']')?;
+            } else {
+                break;
+            }
+        }
+        self.skip_spaces();
+
+        let op = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ]
+        .into_iter()
+        .find(|(symbol, _)| {
+            symbol.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+        });
+        let comparison = match op {
+            Some((symbol, op)) => {
+                self.pos += symbol.len();
+                self.skip_spaces();
+                Some((op, self.literal()?))
+            }
+            None => None,
+        };
+        self.skip_spaces();
+        self.expect(')')?;
+        Ok(Filter { path, comparison })
+    }
+
+    fn literal(&mut self) -> Result<Value, QueryError> {
+        if matches!(self.peek(), Some('\'' | '"')) {
+            return Ok(Value::String(self.string()?));
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match serde_json::from_str::<Value>(&text) {
+            Ok(value) if !text.is_empty() => Ok(value),
+            _ => {
+                self.pos = start;
+                self.error("expected a string, number, true, false or null")
+            }
+        }
+    }
+
+    /// A string in single or double quotes; a backslash escapes the next
+    /// character.
+    fn string(&mut self) -> Result<String, QueryError> {
+        let quote = self.chars[self.pos];
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    self.pos = start;
+                    return self.error("unterminated string");
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c) => text.push(c),
+                        None => return self.error("unterminated string"),
+                    }
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map() -> Value {
+        json!({
+            "actors_data": [
+                { "id": 1, "asset_id": "human", "name": "Ann", "age": 30 },
+                { "id": 2, "asset_id": "orc", "age": 120.0 },
+                { "id": 3, "asset_id": "human", "name": null, "age": 100 }
+            ],
+            "mapStats": { "name": "World", "world time": 5 }
+        })
+    }
+
+    fn select(query: &str) -> Vec<Value> {
+        let map = map();
+        Query::parse(query).unwrap().select(&map).into_iter().cloned().collect()
+    }
+
+    fn ids(query: &str) -> Vec<Value> {
+        select(query).iter().map(|actor| actor["id"].clone()).collect()
+    }
+
+    #[test]
+    fn selects_fields_and_indices() {
+        assert_eq!(select("mapStats.name"), [json!("World")]);
+        assert_eq!(select("$.mapStats['world time']"), [json!(5)]);
+        assert_eq!(select("$['mapStats'][\"name\"]"), [json!("World")]);
+        assert_eq!(ids("actors_data[1]"), [json!(2)]);
+        assert_eq!(select("actors_data[*].id"), [json!(1), json!(2), json!(3)]);
+        assert_eq!(select("mapStats.*"), [json!("World"), json!(5)]);
+        assert!(select("mapStats.missing").is_empty());
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        assert_eq!(ids("actors_data[-1]"), [json!(3)]);
+        assert_eq!(ids("actors_data[-3]"), [json!(1)]);
+        assert!(select("actors_data[-4]").is_empty());
+        assert!(select("actors_data[3]").is_empty());
+    }
+
+    #[test]
+    fn filters_compare_fields_with_literals() {
+        assert_eq!(ids(r#"actors_data[?(@.asset_id=="human")]"#), [json!(1), json!(3)]);
+        assert_eq!(ids("actors_data[?(@.asset_id != 'human')]"), [json!(2)]);
+        assert_eq!(ids("actors_data[?(@.age>=100)]"), [json!(2), json!(3)]);
+        assert_eq!(ids("actors_data[?(@.age < 100)]"), [json!(1)]);
+        assert_eq!(ids("actors_data[?(@.age == 120)]"), [json!(2)]);
+        assert_eq!(ids("actors_data[?(@.name == null)]"), [json!(3)]);
+        assert!(ids("actors_data[?(@.age > 'a')]").is_empty());
+    }
+
+    #[test]
+    fn filters_without_comparison_test_for_the_field() {
+        assert_eq!(ids("actors_data[?(@.name)]"), [json!(1), json!(3)]);
+        assert_eq!(ids("actors_data[?(@['name'])]"), [json!(1), json!(3)]);
+    }
+
+    #[test]
+    fn reports_where_a_query_is_invalid() {
+        let position = |query: &str| Query::parse(query).unwrap_err().position;
+        assert_eq!(position("a..b"), 2);
+        assert_eq!(position("a[-]"), 2);
+        assert_eq!(position("a[x]"), 2);
+        assert_eq!(position("a[0"), 3);
+        assert_eq!(position("a[?(@.b == )]"), 11);
+        assert_eq!(position("a[?(@.b == 'c)]"), 11);
+        assert_eq!(position("a[?(b)]"), 4);
+        assert_eq!(position("a b"), 2);
+    }
+}
@@ -0,0 +1,347 @@
+//! Best-effort recovery of truncated or corrupted maps.
+
+use crate::{
+    format::{self, Format},
+    inflate::{self, DecompressError},
+    map::SECTIONS,
+};
+use serde_json::Value;
+
+/// Result of recovering a damaged map.
+pub struct Recovery {
+    pub json: String,
+    /// Why decompression stopped early, if it did.
+    pub error: Option<DecompressError>,
+    pub decompressed_len: usize,
+    /// Number of bytes dropped from the end of the decompressed JSON.
+    pub discarded: usize,
+    /// Top-level sections found in the recovered JSON, in file order.
+    pub sections: Vec<String>,
+    /// Section that was being written where the data ends.
+    pub incomplete_section: Option<String>,
+}
+
+impl Recovery {
+    /// Known sections that are absent from the recovered map.
+    pub fn missing_sections(&self) -> Vec<&'static str> {
+        SECTIONS
+            .iter()
+            .copied()
+            .filter(|name| !self.sections.iter().any(|s| s == name))
+            .collect()
+    }
+}
+
+/// Decompresses as much of `data` as possible and repairs the JSON so that
+/// it parses. Plain JSON input is only repaired.
+pub fn recover(data: &[u8]) -> Recovery {
+    let (text, error) = match format::detect_format(data) {
+        Format::PlainJson => (data.to_vec(), None),
+        format => {
+            let mut out = Vec::new();
+            let error = inflate::inflate(data, format, &mut out).err();
+            (out, error)
+        }
+    };
+    let decompressed_len = text.len();
+    let text = String::from_utf8_lossy(format::strip_bom(&text));
+
+    let repair = repair_json(&text);
+    let sections = match serde_json::from_str::<Value>(&repair.json) {
+        Ok(Value::Object(_)) => repair.sections,
+        _ => Vec::new(),
+    };
+
+    Recovery {
+        discarded: text.len().saturating_sub(repair.kept),
+        json: repair.json,
+        error,
+        decompressed_len,
+        sections,
+        incomplete_section: repair.incomplete_section,
+    }
+}
+
+struct Repair {
+    json: String,
+    /// Number of bytes of the input kept before the closing suffix.
+    kept: usize,
+    sections: Vec<String>,
+    incomplete_section: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    Key,
+    Colon,
+    CommaOrEnd,
+}
+
+/// A position up to which the input is valid JSON once the open
+/// containers are closed.
+struct SafePoint {
+    pos: usize,
+    closers: Vec<u8>,
+    sections: usize,
+    incomplete: bool,
+}
+
+/// Cuts `text` after the last complete value and closes every string,
+/// array and object that is still open at that point.
+fn repair_json(text: &str) -> Repair {
+    let bytes = text.as_bytes();
+    let mut stack: Vec<u8> = Vec::new();
+    let mut expect = Expect::Value;
+    let mut sections: Vec<String> = Vec::new();
+    let mut safe: Option<SafePoint> = None;
+    let mut i = 0;
+
+    let snapshot = |pos: usize, stack: &[u8], sections: &[String]| SafePoint {
+        pos,
+        closers: stack.iter().rev().copied().collect(),
+        sections: sections.len(),
+        incomplete: stack.len() > 1,
+    };
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match (expect, byte) {
+            (Expect::Value, b'{') | (Expect::Value, b'[') => {
+                stack.push(if byte == b'{' { b'}' } else { b']' });
+                expect = if byte == b'{' { Expect::Key } else { Expect::Value };
+                i += 1;
+                safe = Some(snapshot(i, &stack, &sections));
+            }
+            (Expect::Value, b'"') => match string_end(bytes, i) {
+                Some(end) => {
+                    i = end;
+                    expect = Expect::CommaOrEnd;
+                    safe = Some(snapshot(i, &stack, &sections));
+                }
+                None => {
+                    if stack.is_empty() {
+                        break;
+                    }
+                    let cut = open_string_cut(bytes, i + 1);
+                    let mut json = text[..cut].to_string();
+                    json.push('"');
+                    json.extend(stack.iter().rev().map(|&c| c as char));
+                    return Repair {
+                        json,
+                        kept: cut,
+                        incomplete_section: sections.last().cloned(),
+                        sections,
+                    };
+                }
+            },
+            (Expect::Key, b'"') => match string_end(bytes, i) {
+                Some(end) => {
+                    if stack.len() == 1 {
+                        sections.push(text[i + 1..end - 1].to_string());
+                    }
+                    i = end;
+                    expect = Expect::Colon;
+                }
+                None => break,
+            },
+            (Expect::Colon, b':') => {
+                i += 1;
+                expect = Expect::Value;
+            }
+            (Expect::CommaOrEnd, b',') => {
+                i += 1;
+                expect = match stack.last() {
+                    Some(b'}') => Expect::Key,
+                    _ => Expect::Value,
+                };
+            }
+            (Expect::CommaOrEnd, b'}') | (Expect::CommaOrEnd, b']')
+            | (Expect::Key, b'}')
+            | (Expect::Value, b']') => {
+                if stack.pop() != Some(byte) {
+                    break;
+                }
+                i += 1;
+                expect = Expect::CommaOrEnd;
+                safe = Some(snapshot(i, &stack, &sections));
+                if stack.is_empty() {
+                    break;
+                }
+            }
+            (Expect::Value, _) => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| matches!(b, b',' | b']' | b'}') || b.is_ascii_whitespace())
+                    .map(|n| i + n);
+                match end {
+                    Some(end) if serde_json::from_str::<Value>(&text[i..end]).is_ok() => {
+                        i = end;
+                        expect = Expect::CommaOrEnd;
+                        safe = Some(snapshot(i, &stack, &sections));
+                    }
+                    _ => break,
+                }
+            }
+            _ => break,
+        }
+    }
+
+    match safe {
+        Some(point) => {
+            let mut json = text[..point.pos].to_string();
+            json.extend(point.closers.iter().map(|&c| c as char));
+            // Either the cut is inside the last section kept, or a key was
+            // read after the safe point and its value was lost.
+            let incomplete_section = if point.incomplete {
+                point.sections.checked_sub(1).and_then(|n| sections.get(n)).cloned()
+            } else {
+                sections.get(point.sections).cloned()
+            };
+            sections.truncate(point.sections);
+            Repair {
+                json,
+                kept: point.pos,
+                sections,
+                incomplete_section,
+            }
+        }
+        None => Repair {
+            json: "{}".to_string(),
+            kept: 0,
+            sections: Vec::new(),
+            incomplete_section: None,
+        },
+    }
+}
+
+/// Position just after the closing quote of the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// End of the usable part of an unterminated string, dropping an escape
+/// sequence that was cut in the middle.
+fn open_string_cut(bytes: &[u8], content_start: usize) -> usize {
+    let mut i = content_start;
+    let mut last_complete = content_start;
+    while i < bytes.len() {
+        let len = match bytes[i] {
+            b'\\' if bytes.get(i + 1) == Some(&b'u') => 6,
+            b'\\' => 2,
+            _ => 1,
+        };
+        if i + len > bytes.len() {
+            break;
+        }
+        i += len;
+        last_complete = i;
+    }
+    last_complete
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repaired(text: &str) -> String {
+        let json = repair_json(text).json;
+        assert!(serde_json::from_str::<Value>(&json).is_ok(), "invalid JSON: {}", json);
+        json
+    }
+
+    #[test]
+    fn keeps_complete_json() {
+        let text = r#"{"a": [1, 2], "b": {"c": "d"}}"#;
+        let repair = repair_json(text);
+        assert_eq!(repair.json, text);
+        assert_eq!(repair.kept, text.len());
+        assert_eq!(repair.sections, ["a", "b"]);
+        assert_eq!(repair.incomplete_section, None);
+    }
+
+    #[test]
+    fn closes_a_cut_string() {
+        let repair = repair_json(r#"{"a": 1, "b": "hel"#);
+        assert_eq!(repair.json, r#"{"a": 1, "b": "hel"}"#);
+        assert_eq!(repair.incomplete_section.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn drops_a_cut_escape() {
+        assert_eq!(repaired(r#"{"a": "x\"#), r#"{"a": "x"}"#);
+        assert_eq!(repaired(r#"{"a": "x\u00"#), r#"{"a": "x"}"#);
+        assert_eq!(repaired(r#"{"a": "xé"#), r#"{"a": "xé"}"#);
+        assert_eq!(repaired(r#"{"a": "x\"y"#), r#"{"a": "x\"y"}"#);
+    }
+
+    #[test]
+    fn closes_nested_containers() {
+        assert_eq!(repaired(r#"{"a": [1, {"b": [2, 3"#), r#"{"a": [1, {"b": [2]}]}"#);
+        assert_eq!(repaired(r#"{"a": [[[], {}"#), r#"{"a": [[[], {}]]}"#);
+        assert_eq!(repaired(r#"{"a": {"b": {"#), r#"{"a": {"b": {}}}"#);
+    }
+
+    #[test]
+    fn drops_a_cut_key_or_literal() {
+        assert_eq!(repaired(r#"{"a": 1, "b"#), r#"{"a": 1}"#);
+        assert_eq!(repaired(r#"{"a": true, "b": fal"#), r#"{"a": true}"#);
+        assert_eq!(repaired(r#"{"a": [1.5, 2"#), r#"{"a": [1.5]}"#);
+    }
+
+    #[test]
+    fn reports_the_section_whose_value_was_lost() {
+        let repair = repair_json(r#"{"a": 1, "b": "#);
+        assert_eq!(repair.json, r#"{"a": 1}"#);
+        assert_eq!(repair.sections, ["a"]);
+        assert_eq!(repair.incomplete_section.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn recovers_nothing_from_garbage() {
+        assert_eq!(repair_json("").json, "{}");
+        assert_eq!(repair_json("nonsense").json, "{}");
+    }
+
+    #[test]
+    fn recovers_plain_json_with_a_bom() {
+        let recovery = recover("\u{feff}{\"saveVersion\": 15, \"width\": 2".as_bytes());
+        assert_eq!(recovery.json, r#"{"saveVersion": 15}"#);
+        assert!(recovery.error.is_none());
+        assert_eq!(recovery.sections, ["saveVersion"]);
+        assert_eq!(recovery.incomplete_section.as_deref(), Some("width"));
+        assert!(recovery.missing_sections().contains(&"width"));
+    }
+
+    #[test]
+    fn recovers_the_start_of_a_truncated_stream() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let actors: Vec<String> = (0..200).map(|i| format!(r#"{{"id": {}}}"#, i)).collect();
+        let json = format!(r#"{{"saveVersion": 15, "actors_data": [{}]}}"#, actors.join(", "));
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let recovery = recover(&data[..data.len() / 2]);
+        assert!(recovery.error.is_some());
+        assert!(json.starts_with(recovery.json.trim_end_matches(['}', ']'])));
+        let value: Value = serde_json::from_str(&recovery.json).unwrap();
+        assert!(!value["actors_data"].as_array().unwrap().is_empty());
+        assert_eq!(recovery.incomplete_section.as_deref(), Some("actors_data"));
+    }
+}
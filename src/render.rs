@@ -0,0 +1,288 @@
+//! Pictures of a world with one pixel per tile.
+//!
+//! Kingdom borders are taken from the cities: every city lists the zones it
+//! owns as `{"x": .., "y": ..}` zone coordinates, a zone being 8 x 8 tiles,
+//! and belongs to the kingdom in its `kingdomID`.
+
+use crate::map::SavedMap;
+use serde_json::Value;
+use std::{collections::HashMap, io};
+
+/// Side length of a zone in tiles.
+pub const ZONE_SIZE: usize = 8;
+
+pub type Rgb = [u8; 3];
+
+const BUILT_IN: &[(&str, Rgb)] = &[
+    ("deep_ocean", [50, 85, 170]),
+    ("close_ocean", [62, 105, 195]),
+    ("shallow_waters", [85, 145, 220]),
+    ("pit_deep_ocean", [40, 70, 150]),
+    ("pit_close_ocean", [52, 90, 175]),
+    ("pit_shallow_waters", [70, 125, 200]),
+    ("sand", [240, 222, 150]),
+    ("soil_low", [150, 115, 75]),
+    ("soil_high", [125, 95, 62]),
+    ("hills", [105, 110, 90]),
+    ("mountains", [80, 80, 80]),
+    ("summit", [235, 235, 235]),
+    ("lava0", [255, 190, 40]),
+    ("lava1", [255, 140, 20]),
+    ("lava2", [230, 80, 10]),
+    ("lava3", [190, 40, 10]),
+    ("ice", [200, 230, 250]),
+    ("snow_block", [240, 245, 250]),
+    ("road", [160, 130, 90]),
+    ("field", [205, 175, 95]),
+    ("grass_low", [92, 172, 64]),
+    ("grass_high", [72, 146, 52]),
+    ("savanna_low", [200, 182, 85]),
+    ("savanna_high", [175, 158, 70]),
+    ("jungle_low", [55, 135, 45]),
+    ("jungle_high", [40, 110, 35]),
+    ("desert_low", [238, 205, 125]),
+    ("desert_high", [215, 180, 105]),
+    ("swamp_low", [95, 120, 70]),
+    ("swamp_high", [80, 100, 60]),
+    ("enchanted_low", [185, 120, 210]),
+    ("enchanted_high", [160, 95, 190]),
+    ("corrupted_low", [85, 65, 105]),
+    ("corrupted_high", [70, 52, 88]),
+    ("infernal_low", [160, 45, 30]),
+    ("infernal_high", [130, 35, 25]),
+    ("mushroom_low", [165, 110, 140]),
+    ("mushroom_high", [140, 90, 120]),
+    ("snow_low", [230, 238, 245]),
+    ("snow_high", [210, 222, 235]),
+    ("permafrost_low", [190, 210, 225]),
+    ("permafrost_high", [170, 192, 210]),
+    ("crystal_low", [130, 215, 230]),
+    ("crystal_high", [105, 190, 210]),
+    ("candy_low", [245, 160, 200]),
+    ("candy_high", [225, 135, 180]),
+    ("wasteland_low", [130, 125, 100]),
+    ("wasteland_high", [110, 105, 85]),
+    ("birch_low", [150, 195, 95]),
+    ("birch_high", [130, 175, 80]),
+    ("maple_low", [205, 120, 50]),
+    ("maple_high", [180, 100, 40]),
+    ("rocklands_low", [140, 140, 130]),
+    ("rocklands_high", [120, 120, 110]),
+    ("flower_low", [140, 200, 110]),
+    ("flower_high", [120, 180, 95]),
+];
+
+/// Colours used for the kingdoms' borders, in turn.
+const KINGDOM_COLORS: &[Rgb] = &[
+    [230, 25, 75],
+    [255, 225, 25],
+    [0, 130, 200],
+    [245, 130, 48],
+    [145, 30, 180],
+    [70, 240, 240],
+    [240, 50, 230],
+    [210, 245, 60],
+    [250, 190, 212],
+    [0, 128, 128],
+    [170, 110, 40],
+    [128, 0, 0],
+];
+
+const CITY_COLOR: Rgb = [255, 255, 255];
+const MISSING_COLOR: Rgb = [0, 0, 0];
+
+/// Colour of each tile type.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: HashMap<String, Rgb>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: BUILT_IN
+                .iter()
+                .map(|(name, color)| (name.to_string(), *color))
+                .collect(),
+        }
+    }
+}
+
+impl Palette {
+    pub fn set(&mut self, tile: impl Into<String>, color: Rgb) {
+        self.colors.insert(tile.into(), color);
+    }
+
+    /// Colour of a `tileMap` entry. Entries such as `soil_low:grass_low`
+    /// take the colour of the last known part; unknown tiles get a grey
+    /// derived from their name, so each stays distinguishable.
+    pub fn color(&self, tile: &str) -> Rgb {
+        tile.rsplit(':')
+            .find_map(|part| self.colors.get(part).copied())
+            .unwrap_or_else(|| {
+                let hash = tile
+                    .bytes()
+                    .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+                let shade = 96 + (hash % 96) as u8;
+                [shade, shade, shade]
+            })
+    }
+}
+
+/// Parses a colour written as `#rrggbb`.
+pub fn parse_color(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Outline the land of every kingdom in its colour.
+    pub borders: bool,
+    /// Mark the middle of every city with a white square.
+    pub cities: bool,
+}
+
+/// An RGB picture, top row first.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width as u32,
+            height: height as u32,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width as usize && y < self.height as usize {
+            let pos = (y * self.width as usize + x) * 3;
+            self.pixels[pos..pos + 3].copy_from_slice(&color);
+        }
+    }
+
+    pub fn write_png(&self, writer: impl io::Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Draws the tiles of `map`, or returns `None` if it has no tile data.
+/// Row 0 of the tile data is the bottom of the world, so it ends up as the
+/// last row of the picture.
+pub fn render(map: &SavedMap, palette: &Palette, options: RenderOptions) -> Option<Image> {
+    let rows = map.tiles()?;
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let height = rows.len();
+    let mut image = Image::new(width, height);
+    let flip = |y: usize| height - 1 - y;
+
+    for (y, row) in rows.iter().enumerate() {
+        for x in 0..width {
+            let color = row.get(x).map_or(MISSING_COLOR, |tile| palette.color(tile));
+            image.set(x, flip(y), color);
+        }
+    }
+
+    if options.borders {
+        let owners = kingdom_owners(map, width, height);
+        let owner = |x: usize, y: usize| owners[y * width + x];
+        for y in 0..height {
+            for x in 0..width {
+                let Some(kingdom) = owner(x, y) else {
+                    continue;
+                };
+                let edge = x == 0
+                    || y == 0
+                    || x + 1 == width
+                    || y + 1 == height
+                    || owner(x - 1, y) != Some(kingdom)
+                    || owner(x + 1, y) != Some(kingdom)
+                    || owner(x, y - 1) != Some(kingdom)
+                    || owner(x, y + 1) != Some(kingdom);
+                if edge {
+                    image.set(x, flip(y), KINGDOM_COLORS[kingdom % KINGDOM_COLORS.len()]);
+                }
+            }
+        }
+    }
+
+    if options.cities {
+        for city in map.cities.iter().flatten() {
+            let zones = zones(city.extra.get("zones"));
+            if zones.is_empty() {
+                continue;
+            }
+            let center = |coords: &mut dyn Iterator<Item = usize>| {
+                coords.sum::<usize>() * ZONE_SIZE / zones.len() + ZONE_SIZE / 2
+            };
+            let x = center(&mut zones.iter().map(|z| z.0));
+            let y = center(&mut zones.iter().map(|z| z.1));
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    if let (Some(px), Some(py)) = ((x + dx).checked_sub(1), (y + dy).checked_sub(1))
+                        && py < height
+                    {
+                        image.set(px, flip(py), CITY_COLOR);
+                    }
+                }
+            }
+        }
+    }
+    Some(image)
+}
+
+/// Index of the kingdom owning each tile, row by row, with kingdoms
+/// numbered in the order they appear in the save.
+fn kingdom_owners(map: &SavedMap, width: usize, height: usize) -> Vec<Option<usize>> {
+    let kingdoms: Vec<Value> = map
+        .kingdoms
+        .iter()
+        .flatten()
+        .filter_map(|k| serde_json::to_value(&k.id).ok())
+        .collect();
+    let mut owners = vec![None; width * height];
+    for city in map.cities.iter().flatten() {
+        let Some(kingdom) = city
+            .extra
+            .get("kingdomID")
+            .and_then(|id| kingdoms.iter().position(|k| k == id))
+        else {
+            continue;
+        };
+        for (zone_x, zone_y) in zones(city.extra.get("zones")) {
+            for y in zone_y * ZONE_SIZE..((zone_y + 1) * ZONE_SIZE).min(height) {
+                for x in zone_x * ZONE_SIZE..((zone_x + 1) * ZONE_SIZE).min(width) {
+                    owners[y * width + x] = Some(kingdom);
+                }
+            }
+        }
+    }
+    owners
+}
+
+fn zones(value: Option<&Value>) -> Vec<(usize, usize)> {
+    let coordinate = |zone: &Value, key: &str| {
+        zone.get(key).and_then(Value::as_u64).map(|n| n as usize)
+    };
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|zone| Some((coordinate(zone, "x")?, coordinate(zone, "y")?)))
+        .collect()
+}
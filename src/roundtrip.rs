@@ -0,0 +1,81 @@
+//! Check that a map survives decompress and compress unchanged.
+
+use crate::{
+    deflate::{self, Level},
+    format::{self, Format},
+    inflate,
+    layout::{self, format_json, Indent},
+    validate, PressorError, Result,
+};
+
+/// Bytes of context shown on each side of the first difference.
+const CONTEXT: usize = 40;
+
+/// Outcome of decompressing, exporting and recompressing a map.
+pub struct RoundTrip {
+    pub original_len: usize,
+    pub exported_len: usize,
+    pub recompressed_len: usize,
+    /// First difference between the original and the round-tripped JSON,
+    /// ignoring whitespace.
+    pub exact_mismatch: Option<Mismatch>,
+    /// First difference that parsing the JSON, as `--canonical` does, would
+    /// introduce apart from the key order.
+    pub parsed_mismatch: Option<Mismatch>,
+}
+
+pub struct Mismatch {
+    /// Offset in the JSON with all whitespace between tokens removed.
+    pub offset: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Runs `data` through `decompress` and `compress` in memory and
+/// compares the result token by token with the original JSON.
+pub fn verify(data: &[u8]) -> Result<RoundTrip> {
+    let text = match format::detect_format(data) {
+        Format::PlainJson => crate::text(data)?.to_string(),
+        format => inflate::decompress(data, format)?,
+    };
+    validate::validate_json(&text)?;
+
+    let exported = layout::reindent(&text, Indent::default());
+    let (recompressed, _) = deflate::compress(&exported, Level::default())
+        .map_err(|e| PressorError::io("Compression error", e))?;
+    let restored = inflate::decompress(&recompressed, Format::ZlibJson)?;
+
+    let original = layout::reindent(&text, Indent::Minified);
+    let exact_mismatch = first_mismatch(&original, &layout::reindent(&restored, Indent::Minified));
+    let parsed_export = format_json(&text, Indent::default(), false);
+    let parsed_mismatch =
+        first_mismatch(&original, &layout::reindent(&parsed_export, Indent::Minified));
+
+    Ok(RoundTrip {
+        original_len: data.len(),
+        exported_len: exported.len(),
+        recompressed_len: recompressed.len(),
+        exact_mismatch,
+        parsed_mismatch,
+    })
+}
+
+fn first_mismatch(expected: &str, actual: &str) -> Option<Mismatch> {
+    let offset = expected
+        .bytes()
+        .zip(actual.bytes())
+        .position(|(a, b)| a != b)
+        .or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))?;
+
+    Some(Mismatch {
+        offset,
+        expected: excerpt(expected, offset),
+        actual: excerpt(actual, offset),
+    })
+}
+
+fn excerpt(text: &str, offset: usize) -> String {
+    let start = offset.saturating_sub(CONTEXT);
+    let end = (offset + CONTEXT).min(text.len());
+    String::from_utf8_lossy(&text.as_bytes()[start..end]).into_owned()
+}
@@ -0,0 +1,143 @@
+//! WorldBox save slots: folders such as `saves/save1/` holding the map as
+//! `map.wbox`, the `map.meta` summary shown in the game's load menu and a
+//! `preview.png` picture of the world.
+
+use crate::{atomic, meta::MapMeta, write_map, Map, PressorError, Result, WriteOptions, Written};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub const MAP_FILE: &str = "map.wbox";
+/// Name of a decompressed map written into a folder.
+pub const JSON_FILE: &str = "map.json";
+pub const META_FILE: &str = "map.meta";
+pub const PREVIEW_FILE: &str = "preview.png";
+/// Files of a slot besides its map, copied along with it.
+pub const SLOT_FILES: &[&str] = &[META_FILE, PREVIEW_FILE];
+
+/// Map file of the slot folder `dir`: `map.wbox`, or else the only `.wbox`,
+/// `.wbax` or `.json` file in it.
+pub fn find_map(dir: &Path) -> Result<PathBuf> {
+    let preferred = dir.join(MAP_FILE);
+    if preferred.is_file() {
+        return Ok(preferred);
+    }
+    let entries = fs::read_dir(dir)
+        .map_err(|e| PressorError::io(format!("Failed to read folder {}", dir.display()), e))?;
+    let mut maps: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_map_file(path))
+        .collect();
+    match maps.len() {
+        1 => Ok(maps.remove(0)),
+        _ => Err(PressorError::NotFound(preferred)),
+    }
+}
+
+/// What [`transfer`] did.
+#[derive(Clone, Debug)]
+pub struct Transfer {
+    /// Where the map was written.
+    pub map: PathBuf,
+    pub written: Written,
+    /// Slot files copied along, in the order of [`SLOT_FILES`].
+    pub copied: Vec<PathBuf>,
+    /// Slot files the source folder does not have.
+    pub missing: Vec<&'static str>,
+    /// `map.meta`, if it was updated to match the map.
+    pub meta: Option<PathBuf>,
+}
+
+/// Writes the map of the folder `from` to `map_name` in the folder `to`,
+/// which decides its encoding, and copies the other [`SLOT_FILES`] along.
+/// A map written as [`MAP_FILE`] gets its `map.meta` updated, since the
+/// game shows it in its load menu.
+pub fn transfer(
+    from: &Path,
+    to: &Path,
+    map_name: &str,
+    options: &WriteOptions,
+) -> Result<Transfer> {
+    let map_path = find_map(from)?;
+    fs::create_dir_all(to)
+        .map_err(|e| PressorError::io(format!("Failed to create folder {}", to.display()), e))?;
+    let map = Map::from_bytes(&read(&map_path)?)?;
+    let output = to.join(map_name);
+    let written = write_map(&map, &output, options)?;
+
+    let mut copied = Vec::new();
+    let mut missing = Vec::new();
+    for &name in SLOT_FILES {
+        let source = from.join(name);
+        if !source.is_file() {
+            missing.push(name);
+            continue;
+        }
+        let target = to.join(name);
+        atomic::write(&target, &read(&source)?).map_err(|e| {
+            PressorError::io(format!("File writing error in {}", target.display()), e)
+        })?;
+        copied.push(target);
+    }
+
+    let meta = if map_name == MAP_FILE {
+        let mut meta = read_meta(to)?.unwrap_or_default();
+        meta.update(&map.to_saved_map()?, SystemTime::now());
+        let path = to.join(META_FILE);
+        meta.write(&path)?;
+        Some(path)
+    } else {
+        None
+    };
+    Ok(Transfer {
+        map: output,
+        written,
+        copied,
+        missing,
+        meta,
+    })
+}
+
+/// `path` itself, or the map file inside it if it is a slot folder.
+pub fn resolve_input(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        find_map(path).unwrap_or_else(|_| path.to_path_buf())
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Like [`resolve_input`], but a folder without a map gets a new
+/// `map.wbox`.
+pub fn resolve_output(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        find_map(path).unwrap_or_else(|_| path.join(MAP_FILE))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The `map.meta` of the slot folder `dir`, `None` if it has none.
+pub fn read_meta(dir: &Path) -> Result<Option<MapMeta>> {
+    let path = dir.join(META_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    MapMeta::from_bytes(&read(&path)?).map(Some)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path)
+        .map_err(|e| PressorError::io(format!("File reading error {}", path.display()), e))
+}
+
+/// Whether `path` has the extension of a map: `.wbox`, `.wbax` or `.json`.
+pub fn is_map_file(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase());
+    matches!(ext.as_deref(), Some("wbox") | Some("wbax") | Some("json"))
+}
@@ -0,0 +1,370 @@
+//! Converting maps as they are read, without holding them in memory, for
+//! very large maps and for pipes. `-` stands for stdin or stdout.
+
+use crate::{
+    atomic::AtomicFile,
+    backup,
+    format::{self, Format},
+    inflate,
+    layout::PrettyWriter,
+    DecompressError, Encoding, Indent, JsonError, PressorError, Result,
+};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression, CrcReader, CrcWriter};
+use serde::de::{Deserialize, IgnoredAny};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Path argument that stands for stdin or stdout.
+pub const STDIO: &str = "-";
+
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Settings for [`convert`]. Sorting keys and trying every compression
+/// level need the whole map, so streaming cannot do them.
+#[derive(Clone, Debug)]
+pub struct StreamOptions {
+    /// Compress into a zlib stream, or decompress into JSON.
+    pub encoding: Encoding,
+    /// zlib compression level, from 0 to 9.
+    pub level: u32,
+    /// Layout of the JSON. `None` keeps compressed JSON as it is and
+    /// writes pretty JSON with two spaces.
+    pub indent: Option<Indent>,
+    /// Check that the input of compression is valid JSON.
+    pub validate: bool,
+    /// Read the output file back before it replaces the old one.
+    pub verify: bool,
+    /// Number of backups of a replaced output file to keep, see [`backup`].
+    pub backups: usize,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Compressed,
+            level: 6,
+            indent: None,
+            validate: true,
+            verify: true,
+            backups: 0,
+        }
+    }
+}
+
+/// What [`convert`] did.
+#[derive(Clone, Debug)]
+pub struct Streamed {
+    /// Bytes read and written.
+    pub input: u64,
+    pub output: u64,
+    /// Compression level used, when compressing.
+    pub level: Option<u32>,
+    /// Backup of the output file that was replaced, if one was made.
+    pub backup: Option<PathBuf>,
+}
+
+/// Converts `input` into `output`. Either may be `-` for stdin/stdout. An
+/// output file is replaced atomically like by [`crate::write_map`], but
+/// since the map is not in memory, verification only compares checksums.
+pub fn convert(input: &Path, output: &Path, options: &StreamOptions) -> Result<Streamed> {
+    let read_error = |e| PressorError::io(format!("File reading error {}", input.display()), e);
+    let reader: Box<dyn Read> = if is_stdio(input) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(input).map_err(read_error)?)
+    };
+    let write_error =
+        |e| PressorError::io(format!("File writing error in {}", output.display()), e);
+    let writer = if is_stdio(output) {
+        Output::Stdout(io::stdout().lock())
+    } else {
+        Output::File(AtomicFile::create(output).map_err(write_error)?)
+    };
+
+    let mut reader = CountingReader::new(BufReader::new(reader));
+    let mut writer = CountingWriter::new(BufWriter::new(writer));
+
+    let mut header = Vec::with_capacity(format::HEADER_LEN);
+    (&mut reader)
+        .take(format::HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .map_err(read_error)?;
+    let format = format::detect_format(&header);
+    let (level, checksum) = match options.encoding {
+        Encoding::Json => {
+            if format == Format::PlainJson {
+                return Err(PressorError::WrongFormat(format));
+            }
+            let input = Cursor::new(header).chain(&mut reader);
+            let indent = options.indent.unwrap_or_default();
+            let mut pretty = PrettyWriter::new(CrcWriter::new(&mut writer), indent);
+            inflate::inflate_reader(BufReader::new(input), format, &mut pretty)
+                .map_err(decode_error)?;
+            (None, checksum(pretty.into_inner().crc()))
+        }
+        Encoding::Compressed => {
+            if format.is_compressed() {
+                return Err(PressorError::WrongFormat(format));
+            }
+            // As when reading a whole map, the JSON is compressed without
+            // its BOM.
+            let bom = header.len() - format::strip_bom(&header).len();
+            header.drain(..bom);
+            let input = Cursor::new(header).chain(&mut reader);
+            let checksum = compress(input, &mut writer, options)?;
+            (Some(options.level), checksum)
+        }
+    };
+    writer.flush().map_err(write_error)?;
+    let output_len = writer.count;
+    let output_file = writer
+        .inner
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)
+        .map_err(write_error)?;
+    let backup = match &output_file {
+        Output::File(file) => {
+            if options.verify {
+                verify(file.temp_path(), output, options.encoding, checksum)?;
+            }
+            backup::create(output, options.backups)?
+        }
+        Output::Stdout(_) => None,
+    };
+    output_file.finish().map_err(write_error)?;
+
+    Ok(Streamed {
+        input: reader.count,
+        output: output_len,
+        level,
+        backup,
+    })
+}
+
+/// Compresses `input` into `output` and returns the checksum of the JSON.
+fn compress(mut input: impl Read, output: impl Write, options: &StreamOptions) -> Result<Checksum> {
+    let error = |e| PressorError::io("Compression error", e);
+    let encoder = CrcWriter::new(ZlibEncoder::new(output, Compression::new(options.level)));
+    let mut encoder = match options.indent {
+        Some(indent) => Sink::Reformat(PrettyWriter::new(encoder, indent)),
+        None => Sink::Plain(encoder),
+    };
+    if options.validate {
+        // Every byte the parser reads is passed on to the encoder. The
+        // parser reads a byte at a time, so the tee is buffered to hand the
+        // encoder whole chunks.
+        let tee = TeeReader {
+            inner: &mut input,
+            copy: &mut encoder,
+        };
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(tee));
+        IgnoredAny::deserialize(&mut deserializer)
+            .and_then(|_| deserializer.end())
+            .map_err(|e| match e.io_error_kind() {
+                Some(_) => error(e.into()),
+                None => PressorError::from(JsonError::from_serde(&e, None)),
+            })?;
+    }
+    io::copy(&mut input, &mut encoder).map_err(error)?;
+    let (_, checksum) = encoder.finish().map_err(error)?;
+    Ok(checksum)
+}
+
+/// CRC-32 and length, modulo 2^32, of the JSON that was written.
+type Checksum = (u32, u32);
+
+fn checksum(crc: &flate2::Crc) -> Checksum {
+    (crc.sum(), crc.amount())
+}
+
+/// Reads the written file at `temp` back and checks that its JSON matches
+/// `expected`. The map is not in memory, so only checksums are compared.
+fn verify(temp: &Path, output: &Path, encoding: Encoding, expected: Checksum) -> Result<()> {
+    let failed = |reason: String| PressorError::VerifyFailed {
+        path: output.to_path_buf(),
+        reason,
+    };
+    let file = File::open(temp).map_err(|e| failed(format!("cannot read it back: {}", e)))?;
+    let json: Box<dyn Read> = match encoding {
+        Encoding::Compressed => Box::new(ZlibDecoder::new(BufReader::new(file))),
+        Encoding::Json => Box::new(BufReader::new(file)),
+    };
+    let mut json = CrcReader::new(json);
+    io::copy(&mut json, &mut io::sink())
+        .map_err(|e| failed(format!("cannot decode it: {}", e)))?;
+    if checksum(json.crc()) != expected {
+        return Err(failed("its JSON differs from the converted data".to_string()));
+    }
+    Ok(())
+}
+
+/// Turns an error of [`inflate::inflate_reader`] into the matching
+/// [`PressorError`].
+fn decode_error(error: io::Error) -> PressorError {
+    match error.downcast::<DecompressError>() {
+        Ok(error) => PressorError::InvalidZlib(error),
+        Err(error) => PressorError::io("Decompression error", error),
+    }
+}
+
+/// Where converted data goes. A file only replaces the output path once
+/// the conversion succeeded.
+enum Output {
+    Stdout(io::StdoutLock<'static>),
+    File(AtomicFile),
+}
+
+impl Output {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush(),
+            Output::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Encoder that compressed data goes into, optionally re-indenting it first.
+/// The checksum covers the JSON as it is compressed.
+enum Sink<W: Write> {
+    Plain(CrcWriter<ZlibEncoder<W>>),
+    Reformat(PrettyWriter<CrcWriter<ZlibEncoder<W>>>),
+}
+
+impl<W: Write> Sink<W> {
+    fn finish(self) -> io::Result<(W, Checksum)> {
+        let encoder = match self {
+            Sink::Plain(encoder) => encoder,
+            Sink::Reformat(pretty) => pretty.into_inner(),
+        };
+        let checksum = checksum(encoder.crc());
+        Ok((encoder.into_inner().finish()?, checksum))
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(encoder) => encoder.write(buf),
+            Sink::Reformat(pretty) => pretty.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(encoder) => encoder.flush(),
+            Sink::Reformat(pretty) => pretty.flush(),
+        }
+    }
+}
+
+struct TeeReader<'a, R, W> {
+    inner: &'a mut R,
+    copy: &'a mut W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<'_, R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{write_map, Map, WriteOptions};
+    use std::fs;
+
+    #[test]
+    fn streamed_decompress_matches_whole_map_decompress() {
+        let dir = std::env::temp_dir().join(format!("pressor-stream-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let json = r#"{"mapStats":{"name":"test","big":1.10,"small":1e2},"list":[],"s":"é"}"#;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let input = dir.join("map.wbox");
+        fs::write(&input, encoder.finish().unwrap()).unwrap();
+
+        let streamed = dir.join("streamed.json");
+        let whole = dir.join("whole.json");
+        let options = StreamOptions {
+            encoding: Encoding::Json,
+            ..StreamOptions::default()
+        };
+        convert(&input, &streamed, &options).unwrap();
+        let map = Map::from_bytes(&fs::read(&input).unwrap()).unwrap();
+        write_map(&map, &whole, &WriteOptions::default()).unwrap();
+        let streamed = fs::read_to_string(&streamed).unwrap();
+        let whole = fs::read_to_string(&whole).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(streamed, whole);
+        assert!(streamed.contains("1.10"));
+        assert!(streamed.contains("1e2"));
+        assert!(streamed.contains("é"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Checks run on map JSON before it is compressed.
+
+use crate::map::SavedMap;
+use serde::de::{Deserialize, IgnoredAny};
+use std::fmt;
+
+/// Characters shown on each side of the error position in the snippet.
+const SNIPPET_RADIUS: usize = 40;
+
+/// A JSON syntax error with its position in the text.
+#[derive(Debug)]
+pub struct JsonError {
+    /// False when the JSON is well-formed but a value has the wrong type.
+    pub syntax: bool,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// Part of the offending line with a caret under the error, when the
+    /// text is available.
+    pub snippet: Option<String>,
+}
+
+impl JsonError {
+    pub fn from_serde(error: &serde_json::Error, text: Option<&str>) -> Self {
+        let message = error.to_string();
+        // serde_json appends " at line X column Y", which is reported separately.
+        let message = match message.rfind(" at line ") {
+            Some(pos) => message[..pos].to_string(),
+            None => message,
+        };
+        Self {
+            syntax: !error.is_data(),
+            line: error.line(),
+            column: error.column(),
+            message,
+            snippet: text.map(|text| snippet(text, error.line(), error.column())),
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}: {}",
+            if self.syntax { "Invalid JSON" } else { "Invalid map data" },
+            self.line,
+            self.column,
+            self.message
+        )?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Checks that `text` is a single well-formed JSON value.
+pub fn validate_json(text: &str) -> Result<(), JsonError> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    IgnoredAny::deserialize(&mut deserializer)
+        .and_then(|_| deserializer.end())
+        .map_err(|e| JsonError::from_serde(&e, Some(text)))
+}
+
+/// Checks that `text` is well-formed JSON whose known sections have the
+/// types the game expects.
+pub fn validate_map(text: &str) -> Result<(), JsonError> {
+    validate_json(text)?;
+    SavedMap::from_json(text)
+        .map(|_| ())
+        .map_err(|e| JsonError::from_serde(&e, Some(text)))
+}
+
+fn snippet(text: &str, line: usize, column: usize) -> String {
+    let line_text = text.split('\n').nth(line.saturating_sub(1)).unwrap_or("");
+    let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+    let pos = floor_char_boundary(line_text, column.saturating_sub(1));
+
+    let start = floor_char_boundary(line_text, pos.saturating_sub(SNIPPET_RADIUS));
+    let end = floor_char_boundary(line_text, pos + SNIPPET_RADIUS);
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < line_text.len() { "..." } else { "" };
+
+    let caret_offset = prefix.len() + line_text[start..pos].chars().count();
+    format!(
+        "    {}{}{}\n    {}^",
+        prefix,
+        &line_text[start..end],
+        suffix,
+        " ".repeat(caret_offset)
+    )
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
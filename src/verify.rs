@@ -0,0 +1,115 @@
+//! Checks that a written map reads back as what was meant to be written.
+
+use crate::{Map, PressorError, Result};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Reads the file at `written_path` back and compares its JSON with
+/// `written`, the text that was encoded, byte for byte. If the layout of
+/// `written` differs from `source`, the JSON the map was read from, both
+/// must also hold the same values. `path` is the output path reported in
+/// errors.
+pub fn verify_file(written_path: &Path, path: &Path, written: &str, source: &str) -> Result<()> {
+    let failed = |reason: String| PressorError::VerifyFailed {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let data = fs::read(written_path).map_err(|e| failed(format!("cannot read it back: {}", e)))?;
+    let read = Map::from_bytes(&data).map_err(|e| failed(format!("cannot decode it: {}", e)))?;
+
+    if read.json() != written {
+        let offset = read
+            .json()
+            .bytes()
+            .zip(written.bytes())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| read.json().len().min(written.len()));
+        return Err(failed(format!("it differs from the written JSON at byte {}", offset)));
+    }
+    if written != source {
+        let parse = |json: &str| serde_json::from_str::<Value>(json).ok();
+        // A source that is not valid JSON cannot be compared by value.
+        if let Some(source) = parse(source)
+            && parse(written).as_ref() != Some(&source)
+        {
+            return Err(failed("it does not hold the same JSON values as the map".to_string()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoding, WriteOptions};
+    use std::path::PathBuf;
+
+    const JSON: &str = r#"{"saveVersion":15,"width":64}"#;
+
+    /// Writes `data` to a file of its own and verifies it.
+    fn verify(name: &str, data: &[u8], written: &str, source: &str) -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("pressor-verify-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        fs::write(&file, data).unwrap();
+        let result = verify_file(&file, Path::new("out.wbox"), written, source);
+        fs::remove_file(&file).unwrap();
+        result
+    }
+
+    fn reason(result: Result<()>) -> String {
+        match result {
+            Err(PressorError::VerifyFailed { path, reason }) => {
+                assert_eq!(path, PathBuf::from("out.wbox"));
+                reason
+            }
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn accepts_what_was_written() {
+        let map = Map::from_json(JSON);
+        let options = WriteOptions::default();
+        let (data, _, written) = map.encode(Encoding::Compressed, &options).unwrap();
+        verify("compressed.wbox", &data, &written, JSON).unwrap();
+        verify("plain.json", JSON.as_bytes(), JSON, JSON).unwrap();
+    }
+
+    #[test]
+    fn reports_where_the_json_differs() {
+        let changed = JSON.replace("64", "65");
+        assert_eq!(
+            reason(verify("changed.json", changed.as_bytes(), JSON, JSON)),
+            "it differs from the written JSON at byte 27"
+        );
+        assert_eq!(
+            reason(verify("short.json", &JSON.as_bytes()[..10], JSON, JSON)),
+            "it differs from the written JSON at byte 10"
+        );
+    }
+
+    #[test]
+    fn reports_unreadable_files() {
+        let missing = Path::new("/nonexistent/out.wbox");
+        let missing = verify_file(missing, Path::new("out.wbox"), "", "");
+        assert!(reason(missing).starts_with("cannot read it back: "));
+        let garbage = reason(verify("garbage.wbox", b"\x00\x01garbage", JSON, JSON));
+        assert!(garbage.starts_with("cannot decode it: "), "{}", garbage);
+    }
+
+    #[test]
+    fn compares_values_when_the_layout_changed() {
+        let pretty = "{\n  \"saveVersion\": 15,\n  \"width\": 64\n}";
+        verify("pretty.json", pretty.as_bytes(), pretty, JSON).unwrap();
+
+        let other = pretty.replace("64", "65");
+        assert_eq!(
+            reason(verify("other.json", other.as_bytes(), &other, JSON)),
+            "it does not hold the same JSON values as the map"
+        );
+
+        // Text that is not JSON has no values to compare.
+        verify("invalid.json", pretty.as_bytes(), pretty, "{oops").unwrap();
+    }
+}